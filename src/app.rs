@@ -1,7 +1,11 @@
 use crate::event::Event;
 use crate::kernel::cmd::ModuleCommand;
-use crate::kernel::lkm::KernelModules;
+use crate::kernel::lkm::{
+	KernelModules, ModuleView, SearchMode, BUILTIN_MARKER, LOADED_MARKER,
+	UNLOADED_MARKER,
+};
 use crate::kernel::log::KernelLogs;
+use crate::kernel::snapshot::{ADDED_MARKER, CHANGED_MARKER, REMOVED_MARKER};
 use crate::kernel::Kernel;
 use crate::style::{Style, StyledText, Symbol};
 use crate::util;
@@ -10,13 +14,13 @@ use copypasta_ext::display::DisplayServer as ClipboardDisplayServer;
 use copypasta_ext::ClipboardProviderExt;
 use enum_iterator::Sequence;
 use ratatui::layout::{Alignment, Constraint, Direction, Layout, Rect};
-use ratatui::style::Style as TuiStyle;
+use ratatui::style::{Color, Modifier, Style as TuiStyle};
 use ratatui::text::{Line, Span, Text};
 use ratatui::widgets::{
-	Block as TuiBlock, Borders, Clear, List, ListItem, Paragraph, Row, Table, Wrap,
+	Block as TuiBlock, Borders, Cell, Clear, List, ListItem, Paragraph, Row, Table,
+	Wrap,
 };
 use ratatui::Frame;
-use regex_lite::RegexBuilder;
 use std::fmt::{Debug, Display, Formatter};
 use std::slice::Iter;
 use std::sync::mpsc::Sender;
@@ -26,17 +30,35 @@ use unicode_width::UnicodeWidthStr;
 /// Table header of the module table
 pub const TABLE_HEADER: &[&str] = &[" Module", "Size", "Used by"];
 
+/// Table header of the module table in the available modules view
+pub const AVAILABLE_TABLE_HEADER: &[&str] = &[" Module", "Status", "Path"];
+
+/// Table header of the module table in the configured modules view
+pub const CONFIG_TABLE_HEADER: &[&str] = &[" Module", "Source", "Directive"];
+
 /// Available options in the module management menu
 const OPTIONS: &[(&str, &str)] = &[
 	("unload", "Unload the module"),
 	("reload", "Reload the module"),
 	("blacklist", "Blacklist the module"),
-	("dependent", "Show the dependent modules"),
+	("unblacklist", "Unblacklist the module"),
+	("boot", "Load the module at boot"),
+	("options", "Keep the current options at boot"),
+	("edit", "Edit the options at boot"),
+	("dryrun", "Enable/disable the dry-run mode"),
+	("log", "Show the dry-run log"),
+	("undo", "Undo the last operation"),
+	("history", "Show the operation history"),
+	("dependent", "Show the dependency tree"),
+	("graph", "Export the dependency graph"),
 	("copy", "Copy the module name"),
 	("load", "Load a kernel module"),
 	("clear", "Clear the ring buffer"),
 ];
 
+/// Options that are available in the read-only mode
+const READ_ONLY_OPTIONS: &[&str] = &["dependent", "graph", "copy"];
+
 /// Supported directions of scrolling
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ScrollDirection {
@@ -136,7 +158,9 @@ pub struct App {
 
 impl App {
 	/// Create a new app instance.
-	pub fn new(block: Block, style: Style) -> Self {
+	///
+	/// Options that change the system are not shown in the read-only mode.
+	pub fn new(block: Block, style: Style, read_only: bool) -> Self {
 		Self {
 			selected_block: block,
 			default_block: block,
@@ -147,6 +171,9 @@ impl App {
 			options: StatefulList::with_items(
 				OPTIONS
 					.iter()
+					.filter(|(option, _)| {
+						!read_only || READ_ONLY_OPTIONS.contains(option)
+					})
 					.map(|(option, text)| {
 						(String::from(*option), String::from(*text))
 					})
@@ -215,8 +242,20 @@ impl App {
 	}
 
 	/// Show help message on the information block.
-	pub fn show_help_message(&mut self, kernel_modules: &mut KernelModules<'_>) {
-		let key_bindings: Vec<(&str, &str)> = util::KEY_BINDINGS.to_vec();
+	pub fn show_help_message(&mut self, kernel_modules: &mut KernelModules) {
+		let key_bindings: Vec<(&str, String)> = util::KEY_BINDINGS
+			.iter()
+			.filter_map(|(key, desc)| {
+				let actions = desc
+					.split("; ")
+					.filter(|action| {
+						!kernel_modules.read_only
+							|| !util::COMMAND_KEY_BINDINGS.contains(action)
+					})
+					.collect::<Vec<&str>>();
+				(!actions.is_empty()).then(|| (*key, actions.join("; ")))
+			})
+			.collect();
 		let mut help_text = Vec::new();
 		let mut help_text_raw = Vec::new();
 		for (key, desc) in &key_bindings {
@@ -233,6 +272,8 @@ impl App {
 		}
 		kernel_modules.info_scroll_offset = 0;
 		kernel_modules.command = ModuleCommand::None;
+		kernel_modules.dependency_tree = None;
+		kernel_modules.options_editor = None;
 		kernel_modules.current_name =
 			format!("!Help{}", self.style.unicode.get(Symbol::Helmet));
 		kernel_modules
@@ -240,47 +281,6 @@ impl App {
 			.set(Text::from(help_text), help_text_raw.join("\n"));
 	}
 
-	/// Show dependent modules on the information block.
-	#[allow(clippy::nonminimal_bool)]
-	pub fn show_dependent_modules(
-		&mut self,
-		kernel_modules: &mut KernelModules<'_>,
-	) {
-		let current_line = kernel_modules.default_list.get(kernel_modules.index);
-
-		if let Some(line) = current_line {
-			// If there is no space in "used_modules", return a vector with "-"
-			// Otherwise, split the modules by commas and collect them into a vector
-			let dependent_modules_list = line[2]
-				.rsplit_once(' ')
-				.map_or(vec!["-"], |(_, modules)| modules.split(',').collect());
-
-			if !(dependent_modules_list[0] == "-"
-				|| kernel_modules.current_name.contains("Dependent modules"))
-				|| cfg!(test)
-			{
-				kernel_modules.info_scroll_offset = 0;
-				kernel_modules.command = ModuleCommand::None;
-				kernel_modules.current_name = format!(
-					"!Dependent modules of {}{}",
-					kernel_modules.current_name,
-					self.style.unicode.get(Symbol::HistoricSite)
-				);
-				let mut dependent_modules = Vec::new();
-				for module in &dependent_modules_list {
-					dependent_modules.push(Line::from(vec![
-						Span::styled("-", self.style.colored),
-						Span::styled(format!(" {module}"), self.style.default),
-					]));
-				}
-				kernel_modules.current_info.set(
-					Text::from(dependent_modules),
-					kernel_modules.current_name.clone(),
-				);
-			}
-		}
-	}
-
 	/// Draw a block according to the index.
 	pub fn draw_dynamic_block(
 		&mut self,
@@ -305,6 +305,7 @@ impl App {
 		&self,
 		frame: &mut Frame,
 		area: Rect,
+		search_mode: SearchMode,
 		tx: &Sender<Event<Key>>,
 	) {
 		frame.render_widget(
@@ -323,8 +324,13 @@ impl App {
 						.borders(Borders::ALL)
 						.title(Span::styled(
 							format!(
-								"{}{}",
+								"{}{}{}",
 								self.input_mode,
+								match (self.input_mode, search_mode) {
+									(InputMode::Load, _)
+									| (_, SearchMode::Substring) => String::new(),
+									(_, search_mode) => format!(" ({search_mode})"),
+								},
 								match self.input_mode {
 									InputMode::Load =>
 										self.style.unicode.get(Symbol::Anchor),
@@ -340,7 +346,13 @@ impl App {
 	}
 
 	/// Draw a paragraph widget for showing the kernel information.
-	pub fn draw_kernel_info(&self, frame: &mut Frame, area: Rect, info: &[String]) {
+	pub fn draw_kernel_info(
+		&self,
+		frame: &mut Frame,
+		area: Rect,
+		info: &[String],
+		mode: Option<&str>,
+	) {
 		frame.render_widget(
 			Paragraph::new(Span::raw(&info[1]))
 				.block(
@@ -349,8 +361,9 @@ impl App {
 						.borders(Borders::ALL)
 						.title(Span::styled(
 							format!(
-								"{}{}",
+								"{}{}{}",
 								info[0],
+								mode.map(|v| format!(" [{v}]")).unwrap_or_default(),
 								self.style.unicode.get(Symbol::Gear)
 							),
 							self.style.bold,
@@ -368,26 +381,17 @@ impl App {
 		&mut self,
 		frame: &mut Frame,
 		area: Rect,
-		kernel_modules: &mut KernelModules<'_>,
+		kernel_modules: &mut KernelModules,
 	) {
 		// Filter the module list depending on the input query.
 		let mut kernel_module_list = kernel_modules.default_list.clone();
+		let mut matched_indices = Vec::new();
 		match self.input_mode {
 			InputMode::None | InputMode::Search if !self.input_query.is_empty() => {
-				if kernel_modules.args.regex() {
-					if let Ok(regex) = RegexBuilder::new(&self.input_query)
-						.case_insensitive(true)
-						.build()
-					{
-						kernel_module_list
-							.retain(|module| regex.is_match(module[0].trim_start()))
-					}
-				} else {
-					let input_query = &self.input_query.to_lowercase();
-					kernel_module_list.retain(|module| {
-						module[0].to_lowercase().contains(input_query)
-					});
-				}
+				(kernel_module_list, matched_indices) = kernel_modules
+					.args
+					.search_mode()
+					.filter(kernel_module_list, &self.input_query);
 			}
 			_ => {}
 		}
@@ -401,6 +405,7 @@ impl App {
 			}
 		}
 		kernel_modules.list = kernel_module_list;
+		kernel_modules.restore_selection();
 		// Set the scroll offset for modules.
 		let modules_scroll_offset = area
 			.height
@@ -416,16 +421,89 @@ impl App {
 					.skip(modules_scroll_offset)
 					.enumerate()
 					.map(|(i, item)| {
-						let item = item.iter().map(|v| v.to_string());
-						if Some(i)
+						// Mark the modules that are selected for the batch commands.
+						let name = match item[0].strip_prefix(' ') {
+							Some(v)
+								if kernel_modules.is_marked(
+									v.split_whitespace().next().unwrap_or_default(),
+								) =>
+							{
+								format!("*{v}")
+							}
+							_ => item[0].to_string(),
+						};
+						let mut item = item
+							.iter()
+							.map(|v| Cell::from(v.to_string()))
+							.collect::<Vec<Cell>>();
+						item[0] = Cell::from(name.to_string());
+						// Highlight the characters matched by the fuzzy search.
+						if let Some(indices) =
+							matched_indices.get(i + modules_scroll_offset)
+						{
+							item[0] = Cell::from(Line::from(
+								name.chars()
+									.enumerate()
+									.map(|(j, c)| {
+										if j > 0 && indices.contains(&(j - 1)) {
+											Span::styled(
+												c.to_string(),
+												self.style.bold.add_modifier(
+													Modifier::UNDERLINED,
+												),
+											)
+										} else {
+											Span::raw(c.to_string())
+										}
+									})
+									.collect::<Vec<Span>>(),
+							));
+						}
+						let mut row_style = if Some(i)
 							== kernel_modules
 								.index
 								.checked_sub(modules_scroll_offset)
 						{
-							Row::new(item).style(self.style.default)
+							self.style.default
 						} else {
-							Row::new(item).style(self.style.colored)
+							self.style.colored
+						};
+						// Dim the built-in modules.
+						if kernel_modules.list[i + modules_scroll_offset][0]
+							.ends_with(BUILTIN_MARKER)
+						{
+							row_style = row_style.add_modifier(Modifier::DIM);
+						}
+						// Color the modules that are changed since the snapshot
+						// and highlight the recently loaded or unloaded modules.
+						let row_name =
+							&kernel_modules.list[i + modules_scroll_offset][0];
+						if row_name.ends_with(LOADED_MARKER)
+							|| row_name.ends_with(UNLOADED_MARKER)
+						{
+							row_style = row_style.add_modifier(Modifier::REVERSED);
+						}
+						if let Some(color) = [
+							(LOADED_MARKER, Color::Green),
+							(UNLOADED_MARKER, Color::Red),
+							(ADDED_MARKER, Color::Green),
+							(REMOVED_MARKER, Color::Red),
+							(CHANGED_MARKER, Color::Yellow),
+						]
+						.iter()
+						.find(|(marker, _)| row_name.ends_with(marker))
+						.map(|(_, color)| *color)
+						{
+							row_style = row_style.fg(color);
+							if Some(i)
+								== kernel_modules
+									.index
+									.checked_sub(modules_scroll_offset)
+							{
+								row_style = row_style.add_modifier(Modifier::BOLD);
+							}
 						}
+						Row::new(item).style(row_style)
 					}),
 				&[
 					Constraint::Percentage(30),
@@ -434,8 +512,16 @@ impl App {
 				],
 			)
 			.header(
-				Row::new(TABLE_HEADER.iter().map(|v| v.to_string()))
-					.style(self.style.bold),
+				Row::new(
+					match kernel_modules.view {
+						ModuleView::Loaded => TABLE_HEADER,
+						ModuleView::Available => AVAILABLE_TABLE_HEADER,
+						ModuleView::Config => CONFIG_TABLE_HEADER,
+					}
+					.iter()
+					.map(|v| v.to_string()),
+				)
+				.style(self.style.bold),
 			)
 			.block(
 				TuiBlock::default()
@@ -443,7 +529,8 @@ impl App {
 					.borders(Borders::ALL)
 					.title(Span::styled(
 						format!(
-							"Loaded Kernel Modules {}{}/{}{} {}{}%{}",
+							"{} Kernel Modules {}{}/{}{} {}{}%{}{}",
+							kernel_modules.view,
 							self.style.unicode.get(Symbol::LeftBracket),
 							match kernel_modules.list.len() {
 								0 => kernel_modules.index,
@@ -460,6 +547,16 @@ impl App {
 								0
 							},
 							self.style.unicode.get(Symbol::RightBracket),
+							if kernel_modules.marked.is_empty() {
+								String::new()
+							} else {
+								format!(
+									" {}{} marked{}",
+									self.style.unicode.get(Symbol::LeftBracket),
+									kernel_modules.marked.len(),
+									self.style.unicode.get(Symbol::RightBracket),
+								)
+							},
 						),
 						self.style.bold,
 					)),
@@ -476,7 +573,7 @@ impl App {
 		&mut self,
 		frame: &mut Frame,
 		area: Rect,
-		kernel_modules: &mut KernelModules<'_>,
+		kernel_modules: &mut KernelModules,
 	) {
 		let block_title = format!(
 			"Options ({})",
@@ -554,8 +651,28 @@ impl App {
 		&self,
 		frame: &mut Frame,
 		area: Rect,
-		kernel_modules: &mut KernelModules<'_>,
+		kernel_modules: &mut KernelModules,
 	) {
+		// Keep the selected node of the dependency tree or the selected
+		// parameter of the options editor visible.
+		let selected_line = match (
+			&kernel_modules.dependency_tree,
+			&kernel_modules.options_editor,
+		) {
+			(Some(tree), _) => Some(tree.selected),
+			(_, Some(editor)) if kernel_modules.command.is_none() => {
+				Some(editor.selected_line())
+			}
+			_ => None,
+		};
+		if let Some(selected) = selected_line {
+			let height = area.height.saturating_sub(2) as usize;
+			if selected < kernel_modules.info_scroll_offset {
+				kernel_modules.info_scroll_offset = selected;
+			} else if selected >= kernel_modules.info_scroll_offset + height {
+				kernel_modules.info_scroll_offset = selected + 1 - height;
+			}
+		}
 		frame.render_widget(
 			Paragraph::new(kernel_modules.current_info.get())
 				.block(
@@ -636,7 +753,9 @@ mod tests {
 		let args = ArgMatches::default();
 		let mut kernel_modules =
 			KernelModules::new(ListArgs::new(&args), Style::new(&args));
-		let mut app = App::new(Block::ModuleTable, kernel_modules.style.clone());
+		let mut app =
+			App::new(Block::ModuleTable, kernel_modules.style.clone(), true);
+		assert_eq!(READ_ONLY_OPTIONS.len(), app.options.items.len());
 		app.set_clipboard_contents("test");
 		assert_ne!("x", app.get_clipboard_contents());
 		assert_eq!(app.style.default, app.block_style(Block::ModuleTable));
@@ -648,10 +767,23 @@ mod tests {
 			.draw(|f| {
 				let size = f.size();
 				app.selected_block = Block::UserInput;
-				app.draw_user_input(f, size, &Events::new(100, &kernel_logs).tx);
-				app.draw_kernel_info(f, size, &info::KernelInfo::new().current_info);
+				app.draw_user_input(
+					f,
+					size,
+					kernel_modules.args.search_mode(),
+					&Events::new(100, &kernel_logs).tx,
+				);
+				app.draw_kernel_info(
+					f,
+					size,
+					&info::KernelInfo::new().current_info,
+					Some("read-only"),
+				);
 				app.input_query = String::from("a");
 				app.draw_kernel_modules(f, size, &mut kernel_modules);
+				kernel_modules.args.next_search_mode();
+				kernel_modules.args.next_search_mode();
+				app.draw_kernel_modules(f, size, &mut kernel_modules);
 				app.draw_module_info(f, size, &mut kernel_modules);
 				app.draw_kernel_activities(f, size, &mut kernel_logs);
 			})
@@ -75,6 +75,76 @@ pub fn get_args() -> App {
 				.help("Interpret the module search query as a regular expression")
 				.action(ArgAction::SetTrue),
 		)
+		.arg(
+			Arg::new("fuzzy")
+				.short('F')
+				.long("fuzzy")
+				.help("Rank the modules by fuzzy matching the search query")
+				.conflicts_with("regex")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("protect")
+				.short('P')
+				.long("protect")
+				.value_name("MODULES")
+				.help("Require a typed confirmation to remove the given modules")
+				.action(ArgAction::Append)
+				.num_args(1),
+		)
+		.arg(
+			Arg::new("dry-run")
+				.short('n')
+				.long("dry-run")
+				.help("Show the module commands instead of executing them")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("read-only")
+				.long("read-only")
+				.help("Disable the commands that change the system")
+				.conflicts_with("dry-run")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("escalate")
+				.short('e')
+				.long("escalate")
+				.value_name("HELPER")
+				.value_parser(["none", "sudo", "doas", "pkexec"])
+				.default_value("none")
+				.help("Run the commands with root privileges using the given helper")
+				.num_args(1),
+		)
+		.arg(
+			Arg::new("audit-log")
+				.short('L')
+				.long("audit-log")
+				.value_name("FILE")
+				.help("Append a record of the executed commands to the given file")
+				.num_args(1),
+		)
+		.arg(
+			Arg::new("syslog")
+				.long("syslog")
+				.help("Send the records of the executed commands to syslog")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("control-socket")
+				.long("control-socket")
+				.value_name("PATH")
+				.help("Accept JSON-RPC requests on the given Unix socket")
+				.num_args(1),
+		)
+		.arg(
+			Arg::new("compare")
+				.short('C')
+				.long("compare")
+				.value_name("SNAPSHOT")
+				.help("Highlight the module changes since the given snapshot")
+				.num_args(1),
+		)
 		.subcommand(
 			App::new("sort")
 				.about("Sort kernel modules")
@@ -100,6 +170,187 @@ pub fn get_args() -> App {
 						.action(ArgAction::SetTrue),
 				),
 		)
+		.subcommand(
+			App::new("list")
+				.about("Print the loaded kernel modules")
+				.arg(
+					Arg::new("format")
+						.short('f')
+						.long("format")
+						.value_name("FORMAT")
+						.value_parser(["table", "csv", "json"])
+						.default_value("table")
+						.help("Set the output format")
+						.num_args(1),
+				)
+				.arg(
+					Arg::new("sort")
+						.short('s')
+						.long("sort")
+						.value_name("SORT")
+						.value_parser(["none", "size", "name", "dependent"])
+						.default_value("none")
+						.help("Sort modules by the given column")
+						.num_args(1),
+				)
+				.arg(
+					Arg::new("filter")
+						.short('q')
+						.long("filter")
+						.value_name("QUERY")
+						.help("Show the modules that match the search query")
+						.num_args(1),
+				),
+		)
+		.subcommand(
+			App::new("info")
+				.about("Print the information of a kernel module")
+				.arg(
+					Arg::new("module")
+						.value_name("MODULE")
+						.help("Set the name of the module")
+						.required(true)
+						.num_args(1),
+				)
+				.arg(
+					Arg::new("json")
+						.long("json")
+						.help("Print the information as JSON")
+						.action(ArgAction::SetTrue),
+				),
+		)
+		.subcommand(
+			App::new("logs")
+				.about("Print the kernel logs")
+				.arg(
+					Arg::new("follow")
+						.short('f')
+						.long("follow")
+						.help("Wait for and print the new records")
+						.action(ArgAction::SetTrue),
+				)
+				.arg(
+					Arg::new("level")
+						.short('l')
+						.long("level")
+						.value_name("LEVEL")
+						.value_parser([
+							"emerg", "alert", "crit", "err", "warn", "notice", "info",
+							"debug",
+						])
+						.help("Print the records of the given or a higher level")
+						.num_args(1),
+				)
+				.arg(
+					Arg::new("module")
+						.short('m')
+						.long("module")
+						.value_name("MODULE")
+						.help("Print the records that mention the given module")
+						.num_args(1),
+				)
+				.arg(
+					Arg::new("json")
+						.long("json")
+						.help("Print the records as JSON lines")
+						.action(ArgAction::SetTrue),
+				),
+		)
+		.subcommand(
+			App::new("graph")
+				.about("Export the dependency graph of the loaded modules")
+				.arg(
+					Arg::new("format")
+						.short('f')
+						.long("format")
+						.value_name("FORMAT")
+						.value_parser(["dot", "mermaid", "json"])
+						.default_value("dot")
+						.help("Set the output format")
+						.num_args(1),
+				)
+				.arg(
+					Arg::new("root")
+						.short('r')
+						.long("root")
+						.value_name("MODULE")
+						.help("Export the subgraph of the given module")
+						.num_args(1),
+				),
+		)
+		.subcommand(
+			App::new("serve")
+				.about("Serve the metrics of the kernel modules for Prometheus")
+				.arg(
+					Arg::new("listen")
+						.short('l')
+						.long("listen")
+						.value_name("ADDRESS")
+						.default_value("127.0.0.1:9860")
+						.help("Set the address to listen on")
+						.num_args(1),
+				),
+		)
+		.subcommand(
+			App::new("apply")
+				.about("Apply the module operations of a profile")
+				.arg(
+					Arg::new("profile")
+						.value_name("PROFILE")
+						.help("Set the path of the profile in TOML format")
+						.required(true)
+						.num_args(1),
+				)
+				.arg(
+					Arg::new("yes")
+						.short('y')
+						.long("yes")
+						.help("Apply the plan without a confirmation")
+						.action(ArgAction::SetTrue),
+				)
+				.arg(
+					Arg::new("allow-protected")
+						.short('A')
+						.long("allow-protected")
+						.value_name("MODULES")
+						.help("Allow the plan to change the given protected modules")
+						.value_delimiter(',')
+						.num_args(1),
+				),
+		)
+		.subcommand(
+			App::new("snapshot")
+				.about("Save and compare the snapshots of the loaded modules")
+				.subcommand_required(true)
+				.subcommand(
+					App::new("save")
+						.about("Save a snapshot of the loaded modules")
+						.arg(
+							Arg::new("file")
+								.value_name("FILE")
+								.help("Set the path of the snapshot")
+								.required(true)
+								.num_args(1),
+						),
+				)
+				.subcommand(
+					App::new("diff")
+						.about("Compare the snapshots of the loaded modules")
+						.arg(
+							Arg::new("old")
+								.value_name("OLD")
+								.help("Set the path of the old snapshot")
+								.required(true)
+								.num_args(1),
+						)
+						.arg(
+							Arg::new("new")
+								.value_name("NEW")
+								.help("Set the path of the new snapshot [default: live]")
+								.num_args(1),
+						),
+				),
+		)
 }
 
 #[cfg(test)]
@@ -0,0 +1,396 @@
+use crate::app::{App, Block};
+use crate::event::Event;
+use crate::kernel::cmd::ModuleCommand;
+use crate::kernel::list::{self, ListFormat};
+use crate::kernel::uevent::ModuleEvent;
+use crate::kernel::Kernel;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use termion::event::Key;
+
+/// Error code of the invalid JSON requests
+const PARSE_ERROR: i64 = -32700;
+
+/// Error code of the unknown methods
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Error code of the invalid method parameters
+const INVALID_PARAMS: i64 = -32602;
+
+/// Error code of the requests that cannot be handled
+const SERVER_ERROR: i64 = -32000;
+
+/// Topics of the events that can be subscribed to
+const TOPICS: &[&str] = &["module", "log"];
+
+/// Client that is connected to the control socket
+#[derive(Clone, Debug)]
+pub struct ControlClient {
+	stream: Arc<Mutex<UnixStream>>,
+}
+
+impl ControlClient {
+	/// Send a JSON message to the client.
+	///
+	/// Returns false if the client is disconnected.
+	pub fn send(&self, message: &Value) -> bool {
+		// Message is written at once instead of a write for each token.
+		let line = format!("{message}\n");
+		self.stream
+			.lock()
+			.map(|mut stream| stream.write_all(line.as_bytes()).is_ok())
+			.unwrap_or(false)
+	}
+}
+
+/// JSON-RPC request that is received from the control socket
+#[derive(Clone, Debug)]
+pub struct ControlRequest {
+	pub id: Option<Value>,
+	pub method: String,
+	pub params: Value,
+	pub client: ControlClient,
+}
+
+impl ControlRequest {
+	/// Send the result or error of the request to the client.
+	///
+	/// Requests without an ID are notifications and not responded to.
+	fn respond(&self, response: Result<Value, (i64, String)>) {
+		let Some(id) = &self.id else {
+			return;
+		};
+		self.client.send(&match response {
+			Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+			Err((code, message)) => json!({
+				"jsonrpc": "2.0",
+				"id": id,
+				"error": {"code": code, "message": message},
+			}),
+		});
+	}
+
+	/// Return a string parameter of the request.
+	fn param(&self, name: &str) -> Result<&str, (i64, String)> {
+		self.params[name]
+			.as_str()
+			.ok_or((INVALID_PARAMS, format!("missing parameter: {name}")))
+	}
+}
+
+/// Listen for the JSON-RPC requests on a Unix domain socket.
+///
+/// Requests are sent to the event channel of the terminal UI. Socket is
+/// only accessible by the owner since it can execute the module commands.
+pub fn listen(path: &Path, tx: Sender<Event<Key>>) -> Result<(), Box<dyn Error>> {
+	// Remove the socket that is left from a previous run.
+	if fs::symlink_metadata(path).is_ok_and(|v| v.file_type().is_socket())
+		&& UnixStream::connect(path).is_err()
+	{
+		fs::remove_file(path)?;
+	}
+	let listener = UnixListener::bind(path)?;
+	// Connections are not accepted before the permissions are restricted.
+	fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+	thread::spawn(move || {
+		for stream in listener.incoming().flatten() {
+			let tx = tx.clone();
+			thread::spawn(move || handle_client(stream, tx));
+		}
+	});
+	Ok(())
+}
+
+/// Read the requests of a client line by line.
+fn handle_client(stream: UnixStream, tx: Sender<Event<Key>>) {
+	let Ok(reader) = stream.try_clone() else {
+		return;
+	};
+	let client = ControlClient {
+		stream: Arc::new(Mutex::new(stream)),
+	};
+	for line in BufReader::new(reader).lines().map_while(Result::ok) {
+		if line.trim().is_empty() {
+			continue;
+		}
+		match serde_json::from_str::<Value>(&line) {
+			Ok(request) if request["method"].is_string() => {
+				let request = ControlRequest {
+					id: request.get("id").cloned(),
+					method: request["method"]
+						.as_str()
+						.unwrap_or_default()
+						.to_string(),
+					params: request.get("params").cloned().unwrap_or(json!({})),
+					client: client.clone(),
+				};
+				if tx.send(Event::Control(request)).is_err() {
+					break;
+				}
+			}
+			_ => {
+				client.send(&json!({
+					"jsonrpc": "2.0",
+					"id": null,
+					"error": {"code": PARSE_ERROR, "message": "invalid request"},
+				}));
+			}
+		}
+	}
+}
+
+/// State of the control socket in the terminal UI
+///
+/// Holds the clients that are subscribed to the module and log events and
+/// the command that is prepared by a client.
+#[derive(Debug, Default)]
+pub struct ControlState {
+	clients: Vec<(ControlClient, Vec<String>)>,
+	last_log_line: Option<String>,
+	prepared_command: Option<usize>,
+}
+
+impl ControlState {
+	/// Send a notification to the clients that are subscribed to the topic.
+	///
+	/// Disconnected clients are removed.
+	fn notify(&mut self, topic: &str, params: Value) {
+		let notification =
+			json!({"jsonrpc": "2.0", "method": topic, "params": params});
+		self.clients.retain(|(client, topics)| {
+			!topics.iter().any(|v| v == topic) || client.send(&notification)
+		});
+	}
+
+	/// Notify a module that is loaded or unloaded.
+	pub fn notify_module(&mut self, event: &ModuleEvent) {
+		self.notify(
+			"module",
+			json!({"event": event.action.to_string(), "module": event.module}),
+		);
+	}
+
+	/// Notify the lines of the kernel logs that are added since the last check.
+	pub fn notify_logs(&mut self, output: &str) {
+		let lines = output.lines().collect::<Vec<&str>>();
+		if let Some(last_line) = self.last_log_line.take() {
+			let start = lines
+				.iter()
+				.rposition(|line| *line == last_line)
+				.map_or(0, |v| v + 1);
+			for line in &lines[start..] {
+				self.notify("log", json!({"line": line}));
+			}
+		}
+		self.last_log_line = lines.last().map(|v| v.to_string());
+	}
+}
+
+/// Handle a request of the control socket in the terminal UI.
+///
+/// Returns true if the module list should be refreshed.
+pub fn handle_request(
+	request: &ControlRequest,
+	app: &mut App,
+	kernel: &mut Kernel,
+	state: &mut ControlState,
+) -> bool {
+	let mut refresh = false;
+	let response = match request.method.as_str() {
+		"list" => serde_json::from_str::<Value>(&list::format_list(
+			&kernel.modules.list,
+			ListFormat::Json,
+		))
+		.map_err(|e| (SERVER_ERROR, e.to_string())),
+		"select" => request.param("module").and_then(|name| {
+			app.selected_block = Block::ModuleTable;
+			if kernel.modules.select_module(name) {
+				Ok(json!(kernel.modules.current_name))
+			} else {
+				Err((SERVER_ERROR, format!("module is not listed: {name}")))
+			}
+		}),
+		"search" => request.param("query").map(|query| {
+			app.input_query = query.to_string();
+			kernel.modules.index = 0;
+			json!(query)
+		}),
+		"command" => prepare_command(request, kernel).inspect(|_| {
+			state.prepared_command = Some(kernel.modules.command_generation);
+		}),
+		// Commands that are not prepared by the socket are not confirmed.
+		"confirm"
+			if kernel.modules.command.is_none()
+				|| state.prepared_command
+					!= Some(kernel.modules.command_generation) =>
+		{
+			Err((SERVER_ERROR, String::from("no command to confirm")))
+		}
+		"confirm" => {
+			// Typed confirmation is only set for the commands that require it.
+			if let (Some(confirmation), Some(names)) = (
+				kernel.modules.confirmation.as_mut(),
+				request.params["confirmation"].as_str(),
+			) {
+				*confirmation = names.to_string();
+			}
+			if !kernel.modules.is_confirmed() {
+				Err((SERVER_ERROR, String::from("confirmation does not match")))
+			} else {
+				state.prepared_command = None;
+				refresh = kernel.modules.execute_command();
+				Ok(json!({
+					"executed": refresh,
+					"output": kernel.modules.current_info.raw_text,
+				}))
+			}
+		}
+		"cancel" => Ok(json!(kernel.modules.cancel_execution())),
+		"subscribe" => {
+			let topics = request.params["topics"]
+				.as_array()
+				.map(|topics| {
+					topics
+						.iter()
+						.filter_map(Value::as_str)
+						.filter(|v| TOPICS.contains(v))
+						.map(String::from)
+						.collect::<Vec<String>>()
+				})
+				.unwrap_or_else(|| TOPICS.iter().map(|v| v.to_string()).collect());
+			state.clients.push((request.client.clone(), topics.clone()));
+			Ok(json!(topics))
+		}
+		method => Err((METHOD_NOT_FOUND, format!("unknown method: {method}"))),
+	};
+	request.respond(response);
+	refresh
+}
+
+/// Set the module command of a request and return its confirmation details.
+fn prepare_command(
+	request: &ControlRequest,
+	kernel: &mut Kernel,
+) -> Result<Value, (i64, String)> {
+	let command = ModuleCommand::try_from(request.param("command")?.to_string())
+		.map_err(|_| (INVALID_PARAMS, String::from("unknown command")))?;
+	let command_name = match (command, request.params["module"].as_str()) {
+		(ModuleCommand::Load, Some(module)) => module.to_string(),
+		(_, Some(module)) if !kernel.modules.select_module(module) => {
+			return Err((SERVER_ERROR, format!("module is not listed: {module}")));
+		}
+		_ => String::new(),
+	};
+	if let Some(reason) = kernel.modules.command_rejection(command, &command_name) {
+		return Err((SERVER_ERROR, reason));
+	}
+	kernel.modules.set_current_command(command, command_name);
+	Ok(json!({
+		"command": kernel.modules.command.name(),
+		"module": kernel.modules.current_name,
+		"cmd": kernel.modules.get_current_command().cmd,
+		"confirmation": kernel.modules.required_confirmation().map(|v| v.0),
+	}))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use clap::ArgMatches;
+	use std::sync::mpsc;
+	use std::time::Duration;
+	#[test]
+	fn test_control_socket() -> Result<(), Box<dyn Error>> {
+		let path = crate::util::temp_path("control.sock");
+		let (tx, rx) = mpsc::channel();
+		let file_path = crate::util::temp_path("control.txt");
+		fs::write(&file_path, "")?;
+		assert!(listen(&file_path, tx.clone()).is_err());
+		assert!(file_path.exists());
+		fs::remove_file(file_path)?;
+		listen(&path, tx)?;
+		assert_eq!(0o600, fs::metadata(&path)?.permissions().mode() & 0o777);
+		let mut stream = UnixStream::connect(&path)?;
+		stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+		let mut reader = BufReader::new(stream.try_clone()?);
+		let mut read_response = || -> Result<Value, Box<dyn Error>> {
+			let mut line = String::new();
+			reader.read_line(&mut line)?;
+			Ok(serde_json::from_str(&line)?)
+		};
+		writeln!(stream, "{{")?;
+		assert_eq!(PARSE_ERROR, read_response()?["error"]["code"]);
+		let args = ArgMatches::default();
+		let mut kernel = Kernel::new(&args);
+		let mut app =
+			App::new(Block::ModuleTable, kernel.modules.style.clone(), false);
+		let mut control_state = ControlState::default();
+		for request in [
+			r#"{"jsonrpc": "2.0", "id": 1, "method": "list"}"#,
+			r#"{"jsonrpc": "2.0", "id": 2, "method": "search", "params": {"query": "x"}}"#,
+			r#"{"jsonrpc": "2.0", "id": 3, "method": "select", "params": {"module": "~"}}"#,
+			r#"{"jsonrpc": "2.0", "id": 4, "method": "command", "params": {"command": "load", "module": "~"}}"#,
+			r#"{"jsonrpc": "2.0", "id": 5, "method": "cancel"}"#,
+			r#"{"jsonrpc": "2.0", "id": 8, "method": "confirm", "params": {"confirmation": "~"}}"#,
+			r#"{"jsonrpc": "2.0", "id": 6, "method": "subscribe", "params": {"topics": ["log"]}}"#,
+			r#"{"jsonrpc": "2.0", "method": "cancel"}"#,
+			r#"{"jsonrpc": "2.0", "id": 7, "method": "~"}"#,
+			r#"{"jsonrpc": "2.0", "id": 9, "method": "command", "params": {"command": "load", "module": "a b"}}"#,
+			r#"{"jsonrpc": "2.0", "id": 10, "method": "confirm"}"#,
+		] {
+			// Commands that are started from the keyboard are not confirmed.
+			if request.contains(r#""id": 10"#) {
+				kernel
+					.modules
+					.set_current_command(ModuleCommand::Load, String::from("~"));
+			}
+			writeln!(stream, "{request}")?;
+			if let Event::Control(request) =
+				rx.recv_timeout(Duration::from_secs(5))?
+			{
+				handle_request(&request, &mut app, &mut kernel, &mut control_state);
+			}
+		}
+		assert!(read_response()?["result"].is_array());
+		assert_eq!("x", read_response()?["result"]);
+		assert_eq!("x", app.input_query);
+		assert_eq!(SERVER_ERROR, read_response()?["error"]["code"]);
+		let response = read_response()?;
+		assert_eq!("load", response["result"]["command"]);
+		assert_eq!(Value::Null, response["result"]["confirmation"]);
+		assert!(response["result"]["cmd"]
+			.as_str()
+			.is_some_and(|v| v.starts_with("modprobe ~")));
+		assert_eq!(true, read_response()?["result"]);
+		assert_eq!(SERVER_ERROR, read_response()?["error"]["code"]);
+		assert_eq!(None, kernel.modules.confirmation);
+		assert_eq!(json!(["log"]), read_response()?["result"]);
+		assert_eq!(METHOD_NOT_FOUND, read_response()?["error"]["code"]);
+		assert_eq!(
+			"module name contains a space: a b",
+			read_response()?["error"]["message"]
+		);
+		assert_eq!(
+			"no command to confirm",
+			read_response()?["error"]["message"]
+		);
+		assert_eq!(ModuleCommand::Load, kernel.modules.command);
+		control_state.notify_logs("a\nb");
+		control_state.notify_module(&ModuleEvent {
+			action: crate::kernel::uevent::ModuleAction::Load,
+			module: String::from("a"),
+		});
+		control_state.notify_logs("a\nb\nc");
+		assert_eq!(json!({"line": "c"}), read_response()?["params"]);
+		fs::remove_file(path)?;
+		Ok(())
+	}
+}
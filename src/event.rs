@@ -1,4 +1,7 @@
+use crate::control::ControlRequest;
 use crate::kernel::log::KernelLogs;
+use crate::kernel::uevent::{self, ModuleEvent};
+use crate::util;
 use std::io;
 use std::sync::mpsc;
 use std::thread;
@@ -6,10 +9,15 @@ use std::time::Duration;
 use termion::event::Key;
 use termion::input::TermRead;
 
+/// Timeout of waiting for the terminal input
+const INPUT_TIMEOUT: Duration = Duration::from_millis(100);
+
 /// Terminal event methods
 pub enum Event<I> {
 	Input(I),
 	Kernel(String),
+	Control(ControlRequest),
+	Module(ModuleEvent),
 	Tick,
 }
 
@@ -20,6 +28,7 @@ pub struct Events {
 	pub rx: mpsc::Receiver<Event<Key>>,
 	input_handler: thread::JoinHandle<()>,
 	kernel_handler: thread::JoinHandle<()>,
+	module_handler: thread::JoinHandle<()>,
 	tick_handler: thread::JoinHandle<()>,
 }
 
@@ -34,9 +43,17 @@ impl Events {
 		let input_handler = {
 			let tx = tx.clone();
 			thread::spawn(move || {
-				let stdin = io::stdin();
-				for key in stdin.keys().flatten() {
-					tx.send(Event::Input(key)).unwrap();
+				let mut keys = io::stdin().keys();
+				loop {
+					// Keys are not read while a command prompts on the terminal.
+					let Some(_input) = util::wait_input(INPUT_TIMEOUT) else {
+						continue;
+					};
+					match keys.next() {
+						Some(Ok(key)) => tx.send(Event::Input(key)).unwrap(),
+						Some(Err(_)) => {}
+						None => break,
+					}
 				}
 			})
 		};
@@ -52,6 +69,15 @@ impl Events {
 				thread::sleep(refresh_rate * 10);
 			})
 		};
+		// Handle module events using kernel uevents or '/proc/modules'.
+		let module_handler = {
+			let tx = tx.clone();
+			thread::spawn(move || {
+				uevent::watch(refresh_rate, |event| {
+					tx.send(Event::Module(event)).is_ok()
+				})
+			})
+		};
 		// Create a loop for handling events.
 		let tick_handler = {
 			let tx = tx.clone();
@@ -66,6 +92,7 @@ impl Events {
 			rx,
 			input_handler,
 			kernel_handler,
+			module_handler,
 			tick_handler,
 		}
 	}
@@ -96,6 +123,7 @@ mod tests {
 				}
 				Event::Tick => thread::sleep(Duration::from_millis(100)),
 				Event::Kernel(log) => assert!(!log.is_empty()),
+				Event::Control(_) | Event::Module(_) => {}
 			}
 		}
 		Ok(())
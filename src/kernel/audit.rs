@@ -0,0 +1,188 @@
+use crate::kernel::cmd::ModuleCommand;
+use crate::util;
+use clap::ArgMatches;
+use std::error::Error;
+use std::ffi::CStr;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+use std::process::Output;
+
+/// Socket of the local syslog daemon
+const SYSLOG_SOCKET: &str = "/dev/log";
+
+/// Priority of the syslog messages (authpriv.notice)
+const SYSLOG_PRIORITY: u8 = 10 * 8 + 5;
+
+/// Record of a module operation in the audit log
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AuditRecord {
+	pub timestamp: u64,
+	pub user: String,
+	pub uid: u32,
+	pub euid: u32,
+	pub command: String,
+	pub module: String,
+	pub parameters: String,
+	pub cmd: String,
+	pub status: Option<i32>,
+	pub stderr: String,
+}
+
+impl AuditRecord {
+	/// Create a new record of the executed command.
+	pub fn new(
+		command: ModuleCommand,
+		module: &str,
+		parameters: &str,
+		cmd: &str,
+		output: &io::Result<Output>,
+	) -> Self {
+		let (uid, euid) = unsafe { (libc::getuid(), libc::geteuid()) };
+		Self {
+			timestamp: util::timestamp(),
+			user: Self::user_name(uid),
+			uid,
+			euid,
+			command: command.name().to_string(),
+			module: module.to_string(),
+			parameters: parameters.to_string(),
+			cmd: cmd.to_string(),
+			status: output.as_ref().ok().and_then(|v| v.status.code()),
+			stderr: match output {
+				Ok(output) => String::from_utf8_lossy(&output.stderr)
+					.trim_end()
+					.to_string(),
+				Err(e) => e.to_string(),
+			},
+		}
+	}
+
+	/// Return the name of the user with the given UID.
+	fn user_name(uid: u32) -> String {
+		let passwd = unsafe { libc::getpwuid(uid) };
+		if passwd.is_null() {
+			std::env::var("USER").unwrap_or_default()
+		} else {
+			unsafe { CStr::from_ptr((*passwd).pw_name) }
+				.to_string_lossy()
+				.to_string()
+		}
+	}
+
+	/// Return the timestamp in the RFC 3339 format.
+	pub fn time(&self) -> String {
+		util::format_timestamp(self.timestamp)
+	}
+
+	/// Return the record as a JSON line.
+	pub fn to_json(&self) -> String {
+		serde_json::json!({
+			"timestamp": self.time(),
+			"user": self.user,
+			"uid": self.uid,
+			"euid": self.euid,
+			"command": self.command,
+			"module": self.module,
+			"parameters": self.parameters,
+			"cmd": self.cmd,
+			"status": self.status,
+			"stderr": self.stderr,
+		})
+		.to_string()
+	}
+}
+
+/// Destinations of the audit records
+#[derive(Clone, Debug, Default)]
+pub struct AuditLog {
+	pub path: Option<PathBuf>,
+	pub syslog: bool,
+}
+
+impl AuditLog {
+	/// Create a new audit log from the given arguments.
+	pub fn new(args: &ArgMatches) -> Self {
+		Self {
+			path: args
+				.try_get_one::<String>("audit-log")
+				.ok()
+				.flatten()
+				.map(PathBuf::from),
+			syslog: args.try_get_one::<bool>("syslog").ok().flatten() == Some(&true),
+		}
+	}
+
+	/// Check if the records can be written to the audit log.
+	pub fn check(&self) -> Result<(), Box<dyn Error>> {
+		if let Some(path) = &self.path {
+			OpenOptions::new().create(true).append(true).open(path)?;
+		}
+		if self.syslog {
+			UnixDatagram::unbound()?.connect(SYSLOG_SOCKET)?;
+		}
+		Ok(())
+	}
+
+	/// Append the record to the audit log file and send it to syslog.
+	pub fn write(&self, record: &AuditRecord) -> Result<(), Box<dyn Error>> {
+		let line = record.to_json();
+		if let Some(path) = &self.path {
+			let mut file =
+				OpenOptions::new().create(true).append(true).open(path)?;
+			writeln!(file, "{line}")?;
+		}
+		if self.syslog {
+			UnixDatagram::unbound()?.send_to(
+				format!("<{SYSLOG_PRIORITY}>kmon[{}]: {line}", std::process::id())
+					.as_bytes(),
+				SYSLOG_SOCKET,
+			)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::fs;
+	#[test]
+	fn test_audit_log() -> Result<(), Box<dyn Error>> {
+		let output = std::process::Command::new("sh")
+			.args(["-c", "echo failed >&2; exit 3"])
+			.output();
+		let mut record =
+			AuditRecord::new(ModuleCommand::Unload, "snd", "", "rmmod snd", &output);
+		assert_eq!(Some(3), record.status);
+		assert_eq!("failed", record.stderr);
+		assert_eq!("unload", record.command);
+		record.timestamp = 951782400;
+		assert_eq!("2000-02-29T00:00:00Z", record.time());
+		record.timestamp = 1700000000;
+		assert_eq!("2023-11-14T22:13:20Z", record.time());
+		let path = util::temp_path("audit.log");
+		let audit_log = AuditLog {
+			path: Some(path.clone()),
+			syslog: false,
+		};
+		audit_log.check()?;
+		audit_log.write(&record)?;
+		audit_log.write(&record)?;
+		let contents = fs::read_to_string(&path)?;
+		fs::remove_file(path)?;
+		assert_eq!(2, contents.lines().count());
+		let json: serde_json::Value =
+			serde_json::from_str(contents.lines().next().unwrap_or_default())?;
+		assert_eq!("snd", json["module"]);
+		assert_eq!(3, json["status"]);
+		assert_eq!("2023-11-14T22:13:20Z", json["timestamp"]);
+		let audit_log = AuditLog {
+			path: Some(std::env::temp_dir().join("~/kmon-test-audit.log")),
+			syslog: false,
+		};
+		assert!(audit_log.check().is_err());
+		Ok(())
+	}
+}
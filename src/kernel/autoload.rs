@@ -0,0 +1,77 @@
+use crate::kernel::modinfo;
+use crate::kernel::modprobe::{self, FileWrite};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Configuration file of the modules that are loaded at boot by kmon
+pub const KMON_MODULES_LOAD: &str = "/etc/modules-load.d/kmon.conf";
+
+/// Return the names of the modules in a modules-load.d file.
+pub fn autoload_modules(path: &Path) -> Vec<String> {
+	fs::read_to_string(path)
+		.unwrap_or_default()
+		.lines()
+		.map(str::trim)
+		.filter(|line| !line.is_empty() && !line.starts_with(['#', ';']))
+		.map(modinfo::module_name)
+		.collect()
+}
+
+/// Check if the module is loaded at boot by kmon.
+pub fn is_autoloaded(name: &str) -> bool {
+	autoload_modules(Path::new(KMON_MODULES_LOAD)).contains(&name.to_string())
+}
+
+/// Return the write that adds the module to the modules loaded at boot,
+/// or removes it if it is already added.
+pub fn autoload_write(name: &str) -> FileWrite {
+	let contents = fs::read_to_string(KMON_MODULES_LOAD).unwrap_or_default();
+	let mut lines = contents
+		.lines()
+		.filter(|line| modinfo::module_name(line.trim()) != name)
+		.map(|line| format!("{line}\n"))
+		.collect::<String>();
+	if !is_autoloaded(name) {
+		lines += &format!("{name}\n");
+	}
+	FileWrite::new(Path::new(KMON_MODULES_LOAD), lines)
+}
+
+/// Return the names of the modules that are configured at boot by kmon.
+///
+/// Modules are either loaded at boot or have options in the kmon
+/// configuration of modprobe.
+pub fn persistent_modules() -> HashSet<String> {
+	autoload_modules(Path::new(KMON_MODULES_LOAD))
+		.into_iter()
+		.chain(
+			modprobe::config_entries(&[Path::new(modprobe::KMON_CONFIG).into()])
+				.into_iter()
+				.filter(|entry| entry.directive == "options")
+				.map(|entry| entry.module),
+		)
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::error::Error;
+	#[test]
+	fn test_autoload_modules() -> Result<(), Box<dyn Error>> {
+		let path = crate::util::temp_path("autoload.conf");
+		fs::write(&path, "# comment\n; comment\n\nsnd-hda-intel\n  ext4 \n")?;
+		assert_eq!(
+			vec![String::from("snd_hda_intel"), String::from("ext4")],
+			autoload_modules(&path)
+		);
+		fs::remove_file(path)?;
+		assert_eq!(
+			Path::new("/etc/modules-load.d/kmon.conf"),
+			autoload_write("~").path
+		);
+		assert!(autoload_write("~").contents.ends_with("~\n"));
+		Ok(())
+	}
+}
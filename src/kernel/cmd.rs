@@ -1,4 +1,14 @@
+use crate::kernel::autoload;
+use crate::kernel::graph::ModuleGraph;
+use crate::kernel::impact::UnloadImpact;
+use crate::kernel::modinfo;
+use crate::kernel::modprobe::{self, FileWrite};
+use crate::kernel::sysfs;
 use crate::style::Symbol;
+use crate::util;
+use clap::ArgMatches;
+use std::collections::BTreeMap;
+use std::fs;
 use std::{ffi::OsStr, path::Path};
 
 /// Kernel module related command
@@ -8,6 +18,7 @@ pub struct Command {
 	pub desc: &'static str,
 	pub title: String,
 	pub symbol: Symbol,
+	pub writes: Vec<FileWrite>,
 }
 
 impl Command {
@@ -22,18 +33,37 @@ impl Command {
 				.map_or(title, |(_, title)| title)
 				.to_string(),
 			symbol,
+			writes: Vec::new(),
 		}
 	}
+
+	/// Set the files that are written by the command.
+	///
+	/// Command is built from the writes unless there are none.
+	pub fn with_writes(mut self, writes: Vec<FileWrite>) -> Self {
+		if !writes.is_empty() {
+			self.cmd = modprobe::write_commands(&writes);
+		}
+		self.writes = writes;
+		self
+	}
 }
 
 /// Kernel module management commands
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum ModuleCommand {
+	#[default]
 	None,
 	Load,
 	Unload,
 	Reload,
 	Blacklist,
+	Unblacklist,
+	Toggle,
+	LoadAtBoot,
+	OptionsAtBoot,
+	SetOptions,
+	Undo,
 	Clear,
 }
 
@@ -45,6 +75,10 @@ impl TryFrom<String> for ModuleCommand {
 			"unload" => Ok(Self::Unload),
 			"reload" => Ok(Self::Reload),
 			"blacklist" => Ok(Self::Blacklist),
+			"unblacklist" => Ok(Self::Unblacklist),
+			"toggle" => Ok(Self::Toggle),
+			"boot" => Ok(Self::LoadAtBoot),
+			"options" => Ok(Self::OptionsAtBoot),
 			"clear" => Ok(Self::Clear),
 			_ => Err(()),
 		}
@@ -57,11 +91,7 @@ impl ModuleCommand {
 		match self {
             Self::None => Command::new(String::from(""), "", &format!("Module: {module_name}"), Symbol::None),
             Self::Load => Command::new(
-                if Self::is_module_filename(Path::new(module_name)) {
-					format!("insmod {}", &module_name)
-				} else {
-					format!("modprobe {0} || insmod {0}.ko", &module_name)
-				},
+                Self::load_command(module_name, ""),
                 "Add and remove modules from the Linux Kernel\n
                 This command inserts a module to the kernel.",
                 &format!("Load: {module_name}"), Symbol::Anchor),
@@ -85,10 +115,7 @@ impl ModuleCommand {
                 This command reloads a module, removes and inserts to the kernel.",
                 &format!("Reload: {module_name}"), Symbol::FuelPump),
 			Self::Blacklist => Command::new(
-				format!("if ! grep -q {module} /etc/modprobe.d/blacklist.conf; then
-				  echo 'blacklist {module}' >> /etc/modprobe.d/blacklist.conf
-				  echo 'install {module} /bin/false' >> /etc/modprobe.d/blacklist.conf
-				fi", module = &module_name),
+				String::new(),
 				"This command blacklists a module and any other module that depends on it.\n
 				Blacklisting is a mechanism to prevent the kernel module from loading. \
 				This could be useful if, for example, the associated hardware is not needed, \
@@ -98,8 +125,69 @@ impl ModuleCommand {
 				depends on it or if it is loaded manually. However, there is a workaround for \
 				this behaviour; the install command instructs modprobe to run a custom command \
 				instead of inserting the module in the kernel as normal, so the module will \
-				always fail to load.",
-				&format!("Blacklist: {module_name}"), Symbol::SquareX),
+				always fail to load. The entries are written to /etc/modprobe.d/kmon.conf.",
+				&format!("Blacklist: {module_name}"), Symbol::SquareX)
+				.with_writes(vec![modprobe::blacklist_write(module_name)]),
+			Self::Unblacklist => Command::new(
+				Self::error_cmd(&format!("{module_name} is not blacklisted")),
+				"This command removes the blacklist entries of a module.\n
+				Entries in the configuration file that is managed by kmon are removed \
+				and the entries in the other files are commented out. \
+				Files outside of /etc/modprobe.d are overridden by a file with the \
+				same name in /etc/modprobe.d instead of being edited.",
+				&format!("Unblacklist: {module_name}"), Symbol::SquareX)
+				.with_writes(modprobe::unblacklist_writes(&Self::config_entries(), module_name)),
+			Self::Toggle => Command::new(
+				Self::error_cmd(&format!("{module_name} is not found")),
+				"This command disables or enables a configuration entry.\n
+				Entries in the configuration file that is managed by kmon are removed \
+				and the entries in the other files are commented out or uncommented. \
+				Files outside of /etc/modprobe.d are overridden by a file with the \
+				same name in /etc/modprobe.d instead of being edited.",
+				&format!("Toggle: {module_name}"), Symbol::Gear)
+				.with_writes(
+					modprobe::toggle_write(&Self::config_entries(), module_name)
+						.into_iter()
+						.collect(),
+				),
+			Self::LoadAtBoot => Command::new(
+				String::new(),
+				"This command adds the module to the modules that are loaded at boot \
+				by systemd-modules-load, or removes it if it is already added.\n
+				Modules are listed in /etc/modules-load.d/kmon.conf.",
+				&format!(
+					"{} at boot: {module_name}",
+					if autoload::is_autoloaded(module_name) { "Do not load" } else { "Load" }
+				),
+				Symbol::Anchor)
+				.with_writes(vec![autoload::autoload_write(module_name)]),
+			Self::OptionsAtBoot => Command::new(
+				Self::error_cmd(&format!("{module_name} has no parameters")),
+				"This command sets the current parameter values of the module \
+				as its options at boot.\n
+				Options are written to /etc/modprobe.d/kmon.conf and they are applied \
+				when the module is loaded by modprobe.",
+				&format!("Options at boot: {module_name}"), Symbol::Gear)
+				.with_writes(
+					Some(Self::runtime_options(module_name))
+						.filter(|options| !options.is_empty())
+						.map(|options| modprobe::options_write(module_name, &options))
+						.into_iter()
+						.collect(),
+				),
+			Self::SetOptions => Command::new(
+				Self::error_cmd("no options are edited"),
+				"This command saves the edited options of the module.\n
+				Options are written to /etc/modprobe.d/kmon.conf and they are applied \
+				when the module is loaded by modprobe.",
+				&format!("Set options: {module_name}"), Symbol::Gear),
+			Self::Undo => Command::new(
+				Self::error_cmd("nothing to undo"),
+				"This command reverses the last module operation.\n
+				Unloaded and reloaded modules are loaded with the parameters that \
+				they had before the operation and the blacklist entries that are \
+				added by kmon are removed.",
+				&format!("Undo: {module_name}"), Symbol::FuelPump),
 			Self::Clear => Command::new(
 				String::from("dmesg --clear"),
 				"dmesg: Print or control the kernel ring buffer
@@ -109,17 +197,341 @@ impl ModuleCommand {
         }
 	}
 
+	/// Return the system calls that are performed by the command.
+	///
+	/// Files that are written by the command are not included.
+	pub fn syscalls(self, module_name: &str) -> Vec<String> {
+		match self {
+			Self::Load => {
+				let loaded_modules = ModuleGraph::loaded().unwrap_or_default().nodes;
+				let modules_dir = modinfo::modules_dir();
+				let path = Path::new(module_name);
+				if Self::is_module_filename(path) {
+					vec![path.to_path_buf()]
+				} else {
+					let mut files =
+						modinfo::dependency_files(&modules_dir, module_name);
+					files.extend(modinfo::find_module_file(
+						&modules_dir,
+						module_name,
+					));
+					files
+				}
+				.into_iter()
+				.filter(|path| {
+					!loaded_modules
+						.contains_key(&modinfo::module_name(&path.to_string_lossy()))
+				})
+				.map(|path| format!("finit_module(\"{}\", \"\", 0)", path.display()))
+				.collect()
+			}
+			Self::Unload => {
+				let graph = ModuleGraph::loaded().unwrap_or_default();
+				let impact = UnloadImpact::new(
+					&graph,
+					Path::new(sysfs::SYS_MODULE_DIR),
+					module_name,
+				);
+				[module_name.to_string()]
+					.iter()
+					.chain(&impact.modules)
+					.map(|name| format!("delete_module(\"{name}\", O_NONBLOCK)"))
+					.collect()
+			}
+			Self::Reload => {
+				let mut syscalls = Self::Unload.syscalls(module_name);
+				syscalls.append(&mut Self::Load.syscalls(module_name));
+				syscalls
+			}
+			Self::Clear => {
+				vec![String::from("syslog(SYSLOG_ACTION_CLEAR, NULL, 0)")]
+			}
+			_ => Vec::new(),
+		}
+	}
+
+	/// Return the entries of the modprobe configuration files.
+	fn config_entries() -> Vec<modprobe::ConfigEntry> {
+		modprobe::config_entries(&modprobe::config_files(modprobe::CONFIG_DIRS))
+	}
+
+	/// Return the current parameter values of a module as options.
+	pub fn runtime_options(module_name: &str) -> String {
+		sysfs::module_parameters(Path::new(sysfs::SYS_MODULE_DIR), module_name)
+			.into_iter()
+			.filter(|(_, value)| {
+				!value.is_empty()
+					&& value != "(null)"
+					&& !value.contains(char::is_whitespace)
+			})
+			.map(|(param, value)| format!("{param}={value}"))
+			.collect::<Vec<String>>()
+			.join(" ")
+	}
+
+	/// Return the command that loads the module with the given options.
+	pub fn load_command(module_name: &str, options: &str) -> String {
+		Self::load_command_with(module_name, options.split_whitespace())
+	}
+
+	/// Return the command that loads the module with the given arguments.
+	///
+	/// Each argument is passed as a single option, even if it contains spaces.
+	pub fn load_command_with(
+		module_name: &str,
+		options: impl IntoIterator<Item = impl AsRef<str>>,
+	) -> String {
+		let options = options
+			.into_iter()
+			.map(|option| format!(" {}", util::shell_quote(option.as_ref())))
+			.collect::<String>();
+		if Self::is_module_filename(Path::new(module_name)) {
+			format!("insmod {module_name}{options}")
+		} else {
+			format!(
+				"modprobe {0}{options} || insmod {0}.ko{options}",
+				module_name
+			)
+		}
+	}
+
+	/// Return a command that fails with the given message.
+	fn error_cmd(message: &str) -> String {
+		format!("echo {} >&2; false", util::shell_quote(message))
+	}
+
+	/// Return the name of the command.
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::None => "none",
+			Self::Load => "load",
+			Self::Unload => "unload",
+			Self::Reload => "reload",
+			Self::Blacklist => "blacklist",
+			Self::Unblacklist => "unblacklist",
+			Self::Toggle => "toggle",
+			Self::LoadAtBoot => "boot",
+			Self::OptionsAtBoot => "options",
+			Self::SetOptions => "edit",
+			Self::Undo => "undo",
+			Self::Clear => "clear",
+		}
+	}
+
 	/// Check if module command is set.
 	pub fn is_none(self) -> bool {
 		self == Self::None
 	}
 
+	/// Check if the command removes or disables a loaded module.
+	pub fn is_destructive(self) -> bool {
+		matches!(self, Self::Unload | Self::Reload | Self::Blacklist)
+	}
+
 	/// Check if module name is a filename with suffix 'ko'
 	pub fn is_module_filename(module_name: &Path) -> bool {
 		module_name.extension() == Some(OsStr::new("ko"))
 	}
 }
 
+/// Helper for running the commands with root privileges
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Escalation {
+	#[default]
+	None,
+	Sudo,
+	Doas,
+	Pkexec,
+}
+
+impl TryFrom<&str> for Escalation {
+	type Error = String;
+	fn try_from(s: &str) -> Result<Self, Self::Error> {
+		match s {
+			"none" => Ok(Self::None),
+			"sudo" => Ok(Self::Sudo),
+			"doas" => Ok(Self::Doas),
+			"pkexec" => Ok(Self::Pkexec),
+			_ => Err(format!("unknown escalation helper: {s}")),
+		}
+	}
+}
+
+impl Escalation {
+	/// Create a new escalation helper from the given arguments.
+	pub fn new(args: &ArgMatches) -> Self {
+		args.try_get_one::<String>("escalate")
+			.ok()
+			.flatten()
+			.and_then(|v| Self::try_from(v.as_str()).ok())
+			.unwrap_or_default()
+	}
+
+	/// Return the name of the helper program.
+	pub fn program(self) -> &'static str {
+		match self {
+			Self::None => "",
+			Self::Sudo => "sudo",
+			Self::Doas => "doas",
+			Self::Pkexec => "pkexec",
+		}
+	}
+
+	/// Check if the helper is used for running the commands.
+	///
+	/// Commands are run directly if kmon is already running as root.
+	pub fn is_needed(self) -> bool {
+		self != Self::None && unsafe { libc::geteuid() } != 0
+	}
+
+	/// Check if the helper can run a command without prompting for a
+	/// password.
+	pub fn is_non_interactive(self) -> bool {
+		match self {
+			Self::Sudo | Self::Doas => {
+				util::exec_cmd(self.program(), &["-n", "true"]).is_ok()
+			}
+			Self::Pkexec => false,
+			Self::None => true,
+		}
+	}
+
+	/// Return the program and arguments for running the shell command.
+	pub fn command(self, cmd: &str, non_interactive: bool) -> Vec<String> {
+		let mut args = Vec::new();
+		if self.is_needed() {
+			args.push(self.program().to_string());
+			if non_interactive && self != Self::Pkexec {
+				args.push(String::from("-n"));
+			}
+		}
+		args.extend(["sh", "-c", cmd].map(String::from));
+		args
+	}
+}
+
+/// Modules that require a typed confirmation for the destructive commands
+#[derive(Clone, Debug, Default)]
+pub struct ProtectedModules {
+	modules: BTreeMap<String, String>,
+}
+
+impl ProtectedModules {
+	/// Create a new protected modules instance from the given arguments.
+	///
+	/// Modules backing the root filesystem, the active network interface and
+	/// the console are protected by default.
+	pub fn new(args: &ArgMatches) -> Self {
+		let mut protected = Self::default();
+		if let Ok(mountinfo) = fs::read_to_string("/proc/self/mountinfo") {
+			if let Some((device, fs_type)) = Self::root_mount(&mountinfo) {
+				protected.add(&fs_type, "root filesystem");
+				let device = Path::new("/sys/dev/block").join(device);
+				for module in Self::block_device_modules(&device) {
+					protected.add(&module, "root filesystem device");
+				}
+			}
+		}
+		if let Ok(route) = fs::read_to_string("/proc/net/route") {
+			for interface in Self::default_route_interfaces(&route) {
+				let device = Path::new("/sys/class/net").join(&interface);
+				for module in sysfs::device_modules(&device) {
+					protected
+						.add(&module, &format!("network interface {interface}"));
+				}
+			}
+		}
+		if let Ok(consoles) = fs::read_to_string("/sys/class/tty/console/active") {
+			for console in consoles.split_whitespace() {
+				let mut devices = vec![Path::new("/sys/class/tty").join(console)];
+				// Virtual terminals are drawn on the framebuffer.
+				if console.trim_start_matches("tty").parse::<u32>().is_ok() {
+					devices.push(Path::new("/sys/class/graphics/fb0").to_path_buf());
+				}
+				for module in devices.iter().flat_map(|v| sysfs::device_modules(v)) {
+					protected.add(&module, &format!("console {console}"));
+				}
+			}
+		}
+		if let Ok(Some(modules)) = args.try_get_many::<String>("protect") {
+			for module in modules.flat_map(|v| v.split(',')) {
+				protected.add(module, "configured with --protect");
+			}
+		}
+		protected
+	}
+
+	/// Add a module with the reason of its protection.
+	pub fn add(&mut self, name: &str, reason: &str) {
+		let name = modinfo::module_name(name.trim());
+		if !name.is_empty() {
+			self.modules
+				.entry(name)
+				.or_insert_with(|| reason.to_string());
+		}
+	}
+
+	/// Return the reason of the protection if the command requires a typed
+	/// confirmation for the given module.
+	pub fn requires_confirmation(
+		&self,
+		command: ModuleCommand,
+		name: &str,
+	) -> Option<&str> {
+		if command.is_destructive() {
+			self.modules
+				.get(&modinfo::module_name(name))
+				.map(String::as_str)
+		} else {
+			None
+		}
+	}
+
+	/// Return the device and the filesystem type of the root mount.
+	///
+	/// The last mount is used if the root is mounted over.
+	fn root_mount(mountinfo: &str) -> Option<(String, String)> {
+		mountinfo.lines().rev().find_map(|line| {
+			let (mount, fs) = line.split_once(" - ")?;
+			let mount = mount.split_whitespace().collect::<Vec<&str>>();
+			(mount.get(4) == Some(&"/")).then(|| {
+				(
+					mount.get(2).unwrap_or(&"").to_string(),
+					fs.split_whitespace().next().unwrap_or("").to_string(),
+				)
+			})
+		})
+	}
+
+	/// Return the modules of a block device and the devices below it.
+	fn block_device_modules(device: &Path) -> Vec<String> {
+		let mut modules = sysfs::device_modules(device);
+		for slave in sysfs::dir_entries(&device.join("slaves")) {
+			modules.extend(Self::block_device_modules(
+				&Path::new("/sys/class/block").join(slave),
+			));
+		}
+		modules
+	}
+
+	/// Return the interfaces of the default routes.
+	fn default_route_interfaces(route: &str) -> Vec<String> {
+		route
+			.lines()
+			.skip(1)
+			.filter_map(|line| {
+				let mut columns = line.split_whitespace();
+				match (columns.next(), columns.next()) {
+					(Some(interface), Some("00000000")) => {
+						Some(interface.to_string())
+					}
+					_ => None,
+				}
+			})
+			.collect()
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -128,10 +540,66 @@ mod tests {
 		let module_command = ModuleCommand::None;
 		assert!(module_command == ModuleCommand::None);
 
+		assert!(ModuleCommand::Unload.is_destructive());
+		let mut protected = ProtectedModules::default();
+		protected.add("e1000-e", "test");
+		assert_eq!(
+			Some("test"),
+			protected.requires_confirmation(ModuleCommand::Blacklist, "e1000_e")
+		);
+		assert_eq!(
+			Some("test"),
+			protected.requires_confirmation(ModuleCommand::Unload, "e1000-e")
+		);
+		assert_eq!(
+			None,
+			protected.requires_confirmation(ModuleCommand::Load, "e1000_e")
+		);
+		assert_eq!(
+			Some((String::from("259:2"), String::from("ext4"))),
+			ProtectedModules::root_mount(
+				"22 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw\n\
+				23 22 0:21 / /proc rw shared:5 - proc proc rw"
+			)
+		);
+		assert_eq!(
+			vec![String::from("eno1")],
+			ProtectedModules::default_route_interfaces(
+				"Iface\tDestination\tGateway\n\
+				eno1\t00000000\t0102A8C0\n\
+				eno1\t0002A8C0\t00000000"
+			)
+		);
+		assert!(!ModuleCommand::Load.is_destructive());
+		assert_eq!(Ok(Escalation::Doas), Escalation::try_from("doas"));
+		assert_eq!(Escalation::None, Escalation::new(&ArgMatches::default()));
+		assert_eq!(
+			vec!["sh", "-c", "true"],
+			Escalation::None.command("true", true)
+		);
+		if unsafe { libc::geteuid() } != 0 {
+			assert_eq!(
+				vec!["sudo", "-n", "sh", "-c", "true"],
+				Escalation::Sudo.command("true", true)
+			);
+		}
+		assert_eq!(
+			vec![String::from("finit_module(\"/tmp/~.ko\", \"\", 0)")],
+			ModuleCommand::Load.syscalls("/tmp/~.ko")
+		);
+		assert_eq!(
+			vec![String::from("delete_module(\"~\", O_NONBLOCK)")],
+			ModuleCommand::Unload.syscalls("~")
+		);
+		assert!(ModuleCommand::Blacklist.syscalls("~").is_empty());
 		assert_ne!("", ModuleCommand::None.get("test").title);
 		assert_ne!("", ModuleCommand::Load.get("module").desc);
 		assert_ne!("", ModuleCommand::Unload.get("!command").cmd);
 		assert_ne!("", ModuleCommand::Blacklist.get("~").cmd);
+		assert!(ModuleCommand::Unblacklist.get("~").cmd.ends_with("false"));
+		assert!(ModuleCommand::Toggle.get("~").cmd.ends_with("false"));
+		assert!(ModuleCommand::OptionsAtBoot.get("~").cmd.ends_with("false"));
+		assert_eq!("Load at boot: ~", ModuleCommand::LoadAtBoot.get("~").title);
 
 		assert_eq!(
 			"modprobe test-module || insmod test-module.ko",
@@ -141,6 +609,11 @@ mod tests {
 			"insmod test-module.ko",
 			ModuleCommand::Load.get("test-module.ko").cmd
 		);
+		assert_eq!(
+			"modprobe snd 'index=0' || insmod snd.ko 'index=0'",
+			ModuleCommand::load_command("snd", "index=0")
+		);
+		assert!(ModuleCommand::Undo.get("~").cmd.ends_with("false"));
 
 		assert_eq!(
 			"modprobe -r test-module || rmmod test-module",
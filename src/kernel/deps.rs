@@ -0,0 +1,317 @@
+use crate::kernel::modinfo::{self, ModuleInfo};
+use crate::kernel::sysfs;
+use crate::style::Style;
+use ratatui::style::Modifier;
+use ratatui::text::{Line, Span, Text};
+use std::path::Path;
+
+/// Relation between the modules in the dependency tree
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Relation {
+	Depends,
+	Holders,
+}
+
+impl Relation {
+	/// Return the title of the relation.
+	fn title(self) -> &'static str {
+		match self {
+			Self::Depends => "depends on",
+			Self::Holders => "held by",
+		}
+	}
+
+	/// Return the modules that are related to the given module.
+	///
+	/// Dependencies are read from the module information and the loaded
+	/// modules in sysfs are checked if the information is not available.
+	pub fn modules(self, name: &str) -> Vec<String> {
+		let sys_module_dir = Path::new(sysfs::SYS_MODULE_DIR);
+		match self {
+			Self::Depends => ModuleInfo::read(name)
+				.ok()
+				.and_then(|info| {
+					info.get("depends").map(|depends| {
+						depends
+							.split(',')
+							.filter(|v| !v.is_empty())
+							.map(modinfo::module_name)
+							.collect()
+					})
+				})
+				.unwrap_or_else(|| sysfs::module_dependencies(sys_module_dir, name)),
+			Self::Holders => sysfs::module_holders(sys_module_dir, name),
+		}
+	}
+}
+
+/// Type of a node in the dependency tree
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum NodeKind {
+	Root,
+	Section,
+	Module,
+}
+
+/// Node of the dependency tree
+#[derive(Clone, Debug)]
+struct Node {
+	name: String,
+	kind: NodeKind,
+	relation: Relation,
+	depth: usize,
+	expanded: bool,
+	leaf: bool,
+}
+
+/// Dependency tree of a kernel module
+pub struct DependencyTree {
+	nodes: Vec<Node>,
+	pub selected: usize,
+	related_modules: fn(&str, Relation) -> Vec<String>,
+}
+
+impl DependencyTree {
+	/// Create a new dependency tree for the given module.
+	pub fn new(name: &str) -> Self {
+		Self::with_relations(name, |name, relation| relation.modules(name))
+	}
+
+	/// Create a new dependency tree using the given relation function.
+	fn with_relations(
+		name: &str,
+		related_modules: fn(&str, Relation) -> Vec<String>,
+	) -> Self {
+		let mut tree = Self {
+			nodes: vec![Node {
+				name: name.to_string(),
+				kind: NodeKind::Root,
+				relation: Relation::Depends,
+				depth: 0,
+				expanded: true,
+				leaf: false,
+			}],
+			selected: 0,
+			related_modules,
+		};
+		for relation in [Relation::Depends, Relation::Holders] {
+			tree.nodes.push(Node {
+				name: relation.title().to_string(),
+				kind: NodeKind::Section,
+				relation,
+				depth: 1,
+				expanded: false,
+				leaf: false,
+			});
+			tree.expand(tree.nodes.len() - 1);
+		}
+		tree
+	}
+
+	/// Return the name of the root module.
+	pub fn root(&self) -> &str {
+		&self.nodes[0].name
+	}
+
+	/// Return the name of the selected module.
+	pub fn selected_module(&self) -> Option<&str> {
+		self.nodes
+			.get(self.selected)
+			.filter(|node| node.kind != NodeKind::Section)
+			.map(|node| node.name.as_str())
+	}
+
+	/// Select the next node.
+	pub fn next(&mut self) {
+		self.selected = (self.selected + 1) % self.nodes.len();
+	}
+
+	/// Select the previous node.
+	pub fn previous(&mut self) {
+		self.selected = self.selected.checked_sub(1).unwrap_or(self.nodes.len() - 1);
+	}
+
+	/// Expand or collapse the selected node.
+	pub fn toggle(&mut self) {
+		let node = &self.nodes[self.selected];
+		match (node.kind, node.expanded) {
+			(NodeKind::Root, _) => {}
+			(_, true) => self.collapse(self.selected),
+			(_, false) if !node.leaf => self.expand(self.selected),
+			_ => {}
+		}
+	}
+
+	/// Expand the node at given index.
+	fn expand(&mut self, index: usize) {
+		let node = &self.nodes[index];
+		let name = match node.kind {
+			NodeKind::Section => self.root(),
+			_ => &node.name,
+		};
+		// Skip the ancestors of the node to prevent cycles.
+		let ancestors = self.ancestors(index);
+		let children = (self.related_modules)(name, node.relation)
+			.into_iter()
+			.filter(|v| !ancestors.contains(&v.as_str()) && v != self.root())
+			.map(|name| Node {
+				name,
+				kind: NodeKind::Module,
+				relation: node.relation,
+				depth: node.depth + 1,
+				expanded: false,
+				leaf: false,
+			})
+			.collect::<Vec<Node>>();
+		let node = &mut self.nodes[index];
+		node.expanded = true;
+		node.leaf = node.kind == NodeKind::Module && children.is_empty();
+		self.nodes.splice(index + 1..index + 1, children);
+	}
+
+	/// Collapse the node at given index.
+	fn collapse(&mut self, index: usize) {
+		let depth = self.nodes[index].depth;
+		let end = self.nodes[index + 1..]
+			.iter()
+			.position(|node| node.depth <= depth)
+			.map_or(self.nodes.len(), |v| index + 1 + v);
+		self.nodes.drain(index + 1..end);
+		self.nodes[index].expanded = false;
+	}
+
+	/// Return the names of the module ancestors of the node at given index.
+	fn ancestors(&self, index: usize) -> Vec<&str> {
+		let mut depth = self.nodes[index].depth;
+		self.nodes[..index]
+			.iter()
+			.rev()
+			.filter(|node| {
+				let is_ancestor = node.depth < depth;
+				if is_ancestor {
+					depth = node.depth;
+				}
+				is_ancestor && node.kind == NodeKind::Module
+			})
+			.map(|node| node.name.as_str())
+			.collect()
+	}
+
+	/// Check if the node at given index is the last one of its siblings.
+	fn is_last(&self, index: usize) -> bool {
+		let depth = self.nodes[index].depth;
+		self.nodes[index + 1..]
+			.iter()
+			.find(|node| node.depth <= depth)
+			.is_none_or(|node| node.depth < depth)
+	}
+
+	/// Return the tree prefix of the node at given index.
+	fn prefix(&self, index: usize) -> String {
+		let depth = self.nodes[index].depth;
+		let mut prefix = String::new();
+		for level in 1..depth {
+			let ancestor = self.nodes[..index]
+				.iter()
+				.rposition(|node| node.depth == level)
+				.unwrap_or_default();
+			prefix += if self.is_last(ancestor) {
+				"   "
+			} else {
+				"│  "
+			};
+		}
+		if depth > 0 {
+			prefix += if self.is_last(index) {
+				"└─ "
+			} else {
+				"├─ "
+			};
+		}
+		prefix
+	}
+
+	/// Render the tree as styled and raw text.
+	pub fn render(&self, style: &Style) -> (Text<'static>, String) {
+		let mut lines = Vec::new();
+		let mut raw_lines = Vec::new();
+		for (index, node) in self.nodes.iter().enumerate() {
+			let marker = match (node.kind, node.expanded, node.leaf) {
+				(NodeKind::Root, _, _) | (_, _, true) => "",
+				(_, false, _) => "[+] ",
+				(_, true, _) => "[-] ",
+			};
+			let prefix = self.prefix(index);
+			let label = format!("{marker}{}", node.name);
+			lines.push(Line::from(vec![
+				Span::styled(prefix.to_string(), style.colored),
+				Span::styled(
+					label.to_string(),
+					if index == self.selected {
+						style.default.add_modifier(Modifier::REVERSED)
+					} else if node.kind == NodeKind::Module {
+						style.default
+					} else {
+						style.bold
+					},
+				),
+			]));
+			raw_lines.push(format!("{prefix}{label}"));
+		}
+		(Text::from(lines), raw_lines.join("\n"))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use clap::ArgMatches;
+	#[test]
+	fn test_dependency_tree() {
+		let mut tree = DependencyTree::with_relations("snd_hda_intel", |name, r| {
+			match (name, r) {
+				("snd_hda_intel", Relation::Depends) => vec!["snd_hda_codec", "snd"],
+				("snd_hda_codec", Relation::Depends) => vec!["snd"],
+				("snd", Relation::Depends) => vec!["snd_hda_intel"],
+				_ => vec![],
+			}
+			.into_iter()
+			.map(String::from)
+			.collect()
+		});
+		let render = |tree: &DependencyTree| {
+			tree.render(&Style::new(&ArgMatches::default())).1
+		};
+		assert_eq!(
+			"snd_hda_intel\n├─ [-] depends on\n│  ├─ [+] snd_hda_codec\n\
+			│  └─ [+] snd\n└─ [-] held by",
+			render(&tree)
+		);
+		tree.next();
+		assert_eq!(None, tree.selected_module());
+		tree.next();
+		assert_eq!(Some("snd_hda_codec"), tree.selected_module());
+		tree.toggle();
+		assert_eq!(
+			"snd_hda_intel\n├─ [-] depends on\n│  ├─ [-] snd_hda_codec\n\
+			│  │  └─ [+] snd\n│  └─ [+] snd\n└─ [-] held by",
+			render(&tree)
+		);
+		tree.next();
+		tree.toggle();
+		assert!(render(&tree).contains("│  │  └─ snd\n"));
+		tree.previous();
+		tree.toggle();
+		assert_eq!(
+			"snd_hda_intel\n├─ [-] depends on\n│  ├─ [+] snd_hda_codec\n\
+			│  └─ [+] snd\n└─ [-] held by",
+			render(&tree)
+		);
+		tree.previous();
+		tree.toggle();
+		assert_eq!(
+			"snd_hda_intel\n├─ [+] depends on\n└─ [-] held by",
+			render(&tree)
+		);
+	}
+}
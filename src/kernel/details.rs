@@ -0,0 +1,240 @@
+use crate::kernel::deps::Relation;
+use crate::kernel::modinfo::{self, ModuleInfo};
+use crate::kernel::sysfs;
+use clap::ArgMatches;
+use serde_json::{json, Map, Value};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+/// Device that is bound to a driver of a module
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BoundDevice {
+	pub device: String,
+	pub driver: String,
+	pub interfaces: Vec<(String, String)>,
+}
+
+/// Structured information and runtime state of a kernel module
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ModuleDetails {
+	pub name: String,
+	pub info: Option<ModuleInfo>,
+	pub parameters: Vec<(String, String)>,
+	pub holders: Vec<String>,
+	pub dependencies: Vec<String>,
+	pub devices: Vec<BoundDevice>,
+}
+
+impl ModuleDetails {
+	/// Read the details of a loadable or built-in module.
+	pub fn read(name: &str) -> Result<Self, Box<dyn Error>> {
+		let name = modinfo::module_name(name);
+		Self::collect(
+			&name,
+			ModuleInfo::read(&name).ok(),
+			Relation::Depends.modules(&name),
+			Path::new(sysfs::SYS_MODULE_DIR),
+		)
+	}
+
+	/// Collect the runtime state of a module from sysfs.
+	///
+	/// Modules that have neither information nor a sysfs entry are not found.
+	fn collect(
+		name: &str,
+		info: Option<ModuleInfo>,
+		dependencies: Vec<String>,
+		sys_module_dir: &Path,
+	) -> Result<Self, Box<dyn Error>> {
+		if info.is_none() && !sys_module_dir.join(name).exists() {
+			return Err(format!("module not found: {name}").into());
+		}
+		Ok(Self {
+			name: name.to_string(),
+			info,
+			parameters: sysfs::module_parameters(sys_module_dir, name),
+			holders: sysfs::module_holders(sys_module_dir, name),
+			dependencies,
+			devices: sysfs::module_devices(sys_module_dir, name)
+				.into_iter()
+				.map(|(driver, device)| BoundDevice {
+					device: device
+						.file_name()
+						.map(|v| v.to_string_lossy().to_string())
+						.unwrap_or_default(),
+					driver,
+					interfaces: sysfs::device_interfaces(&device),
+				})
+				.collect(),
+		})
+	}
+
+	/// Return the details as JSON.
+	///
+	/// Fields that occur more than once in the module information are
+	/// returned as arrays.
+	pub fn to_json(&self) -> Value {
+		let mut fields = Map::new();
+		let mut parameters = Vec::new();
+		if let Some(info) = &self.info {
+			for (key, value) in &info.fields {
+				if matches!(key.as_str(), "parm" | "parmtype") && value.contains(':')
+				{
+					continue;
+				}
+				match fields.get_mut(key) {
+					Some(Value::Array(values)) => values.push(json!(value)),
+					Some(field) => *field = json!([field.clone(), value]),
+					None => {
+						fields.insert(key.to_string(), json!(value));
+					}
+				}
+			}
+			for (name, desc, param_type) in info.parameters() {
+				parameters.push(json!({
+					"name": name,
+					"description": desc,
+					"type": param_type,
+					"value": self.parameter(name),
+				}));
+			}
+		}
+		// Parameters that are only found in sysfs have no description.
+		for (name, value) in &self.parameters {
+			if !parameters.iter().any(|v| v["name"] == name.as_str()) {
+				parameters.push(json!({
+					"name": name,
+					"description": null,
+					"type": null,
+					"value": value,
+				}));
+			}
+		}
+		let devices = self
+			.devices
+			.iter()
+			.map(|device| {
+				json!({
+					"device": device.device,
+					"driver": device.driver,
+					"interfaces": device
+						.interfaces
+						.iter()
+						.map(|(class, name)| format!("{class}/{name}"))
+						.collect::<Vec<String>>(),
+				})
+			})
+			.collect::<Vec<Value>>();
+		json!({
+			"name": self.name,
+			"filename": self.info.as_ref().map(|v| v.filename.to_string()),
+			"modinfo": fields,
+			"parameters": parameters,
+			"holders": self.holders,
+			"dependencies": self.dependencies,
+			"devices": devices,
+		})
+	}
+
+	/// Return the current value of a parameter.
+	fn parameter(&self, name: &str) -> Option<&str> {
+		self.parameters
+			.iter()
+			.find(|(param, _)| param == name)
+			.map(|(_, value)| value.as_str())
+	}
+}
+
+/// Implementation of Display for formatting the details as modinfo does
+impl Display for ModuleDetails {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match &self.info {
+			Some(info) => writeln!(f, "{info}")?,
+			None => writeln!(f, "{:<16}{}", "name:", self.name)?,
+		}
+		for (param, value) in &self.parameters {
+			writeln!(f, "{:<16}{param}={value}", "parameter:")?;
+		}
+		for (title, modules) in [
+			("holders:", &self.holders),
+			("dependencies:", &self.dependencies),
+		] {
+			if !modules.is_empty() {
+				writeln!(f, "{title:<16}{}", modules.join(","))?;
+			}
+		}
+		for device in &self.devices {
+			write!(f, "{:<16}{} ({})", "device:", device.device, device.driver)?;
+			for (class, name) in &device.interfaces {
+				write!(f, " {class}/{name}")?;
+			}
+			writeln!(f)?;
+		}
+		Ok(())
+	}
+}
+
+/// Print the details of a module for the `info` subcommand.
+pub fn run(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+	let name = matches
+		.get_one::<String>("module")
+		.ok_or("module is not given")?;
+	let details = ModuleDetails::read(name)?;
+	if matches.get_flag("json") {
+		println!("{}", details.to_json());
+	} else {
+		print!("{details}");
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::fs;
+	#[test]
+	fn test_module_details() -> Result<(), Box<dyn Error>> {
+		let dir = crate::util::temp_path("details");
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(dir.join("snd/parameters"))?;
+		fs::create_dir_all(dir.join("snd/holders/snd_hda_intel"))?;
+		fs::write(dir.join("snd/parameters/index"), "0\n")?;
+		fs::write(dir.join("snd/parameters/slots"), "N\n")?;
+		let info = ModuleInfo {
+			filename: String::from("/lib/modules/snd.ko"),
+			fields: [
+				("alias", "char-major-116-*"),
+				("alias", "sound-service-?-0"),
+				("depends", "soundcore"),
+				("parm", "index:Index value"),
+				("parmtype", "index:int"),
+			]
+			.iter()
+			.map(|(k, v)| (k.to_string(), v.to_string()))
+			.collect(),
+		};
+		let details = ModuleDetails::collect(
+			"snd",
+			Some(info),
+			vec![String::from("soundcore")],
+			&dir,
+		)?;
+		assert_eq!(vec!["snd_hda_intel"], details.holders);
+		let json = details.to_json();
+		assert_eq!("sound-service-?-0", json["modinfo"]["alias"][1]);
+		assert_eq!("soundcore", json["modinfo"]["depends"]);
+		assert_eq!("int", json["parameters"][0]["type"]);
+		assert_eq!("0", json["parameters"][0]["value"]);
+		assert_eq!(Value::Null, json["parameters"][1]["description"]);
+		assert!(details.to_string().ends_with(
+			"parameter:      index=0\n\
+			parameter:      slots=N\n\
+			holders:        snd_hda_intel\n\
+			dependencies:   soundcore\n"
+		));
+		assert!(ModuleDetails::collect("~", None, Vec::new(), &dir).is_err());
+		fs::remove_dir_all(dir)?;
+		Ok(())
+	}
+}
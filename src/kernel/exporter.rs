@@ -0,0 +1,346 @@
+use crate::kernel::graph::{GraphNode, ModuleGraph};
+use crate::kernel::log::{self, LogRecord, LOG_LEVELS};
+use crate::kernel::modinfo;
+use crate::kernel::uevent::{self, ModuleAction, ModuleEvent};
+use clap::ArgMatches;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Content type of the metrics in the Prometheus text format
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Taint flags of the kernel in the order of their bits
+const TAINT_FLAGS: &[char] = &[
+	'P', 'F', 'S', 'R', 'M', 'B', 'U', 'D', 'A', 'W', 'C', 'I', 'O', 'E', 'L', 'K',
+	'X', 'T', 'N', 'J',
+];
+
+/// Counters of the module load and unload events
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ModuleEvents {
+	pub loads: u64,
+	pub unloads: u64,
+}
+
+impl ModuleEvents {
+	/// Count the given module event.
+	pub fn add(&mut self, event: &ModuleEvent) {
+		match event.action {
+			ModuleAction::Load => self.loads += 1,
+			ModuleAction::Unload => self.unloads += 1,
+		}
+	}
+}
+
+/// Counters of the kernel log messages in the order of the log levels
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LogMessages {
+	pub levels: [u64; LOG_LEVELS.len()],
+}
+
+impl LogMessages {
+	/// Count the given log record.
+	pub fn add(&mut self, record: &LogRecord) {
+		if let Some(index) = LOG_LEVELS.iter().position(|v| *v == record.level) {
+			self.levels[index] += 1;
+		}
+	}
+}
+
+/// Write a metric family with its help text and type.
+fn write_family(
+	output: &mut String,
+	name: &str,
+	metric_type: &str,
+	help: &str,
+	samples: &[(String, u64)],
+) {
+	let _ = writeln!(output, "# HELP {name} {help}");
+	let _ = writeln!(output, "# TYPE {name} {metric_type}");
+	for (labels, value) in samples {
+		let _ = writeln!(output, "{name}{labels} {value}");
+	}
+}
+
+/// Return the metrics in the Prometheus text format.
+pub fn render_metrics(
+	graph: &ModuleGraph,
+	builtin_modules: usize,
+	tainted: u64,
+	messages: &LogMessages,
+	events: &ModuleEvents,
+) -> String {
+	let mut output = String::new();
+	write_family(
+		&mut output,
+		"kmon_modules",
+		"gauge",
+		"Number of the kernel modules.",
+		&[
+			(String::from("{state=\"loaded\"}"), graph.nodes.len() as u64),
+			(String::from("{state=\"builtin\"}"), builtin_modules as u64),
+		],
+	);
+	let module_samples = |value: fn(&GraphNode) -> u64| {
+		graph
+			.nodes
+			.iter()
+			.map(|(module, node)| (format!("{{module=\"{module}\"}}"), value(node)))
+			.collect::<Vec<(String, u64)>>()
+	};
+	write_family(
+		&mut output,
+		"kmon_module_size_bytes",
+		"gauge",
+		"Memory size of the loaded module.",
+		&module_samples(|v| v.size),
+	);
+	write_family(
+		&mut output,
+		"kmon_module_refcount",
+		"gauge",
+		"Number of the references to the loaded module.",
+		&module_samples(|v| u64::from(v.refcount)),
+	);
+	write_family(
+		&mut output,
+		"kmon_kernel_tainted",
+		"gauge",
+		"Taint value of the kernel.",
+		&[(String::new(), tainted)],
+	);
+	write_family(
+		&mut output,
+		"kmon_kernel_taint",
+		"gauge",
+		"Taint bits of the kernel.",
+		&TAINT_FLAGS
+			.iter()
+			.enumerate()
+			.map(|(bit, flag)| {
+				(
+					format!("{{bit=\"{bit}\",flag=\"{flag}\"}}"),
+					(tainted >> bit) & 1,
+				)
+			})
+			.collect::<Vec<(String, u64)>>(),
+	);
+	write_family(
+		&mut output,
+		"kmon_kernel_log_messages_total",
+		"counter",
+		"Number of the kernel log messages.",
+		&LOG_LEVELS
+			.iter()
+			.zip(messages.levels)
+			.map(|(level, count)| (format!("{{level=\"{level}\"}}"), count))
+			.collect::<Vec<(String, u64)>>(),
+	);
+	write_family(
+		&mut output,
+		"kmon_module_events_total",
+		"counter",
+		"Number of the module load and unload events.",
+		&[
+			(String::from("{event=\"load\"}"), events.loads),
+			(String::from("{event=\"unload\"}"), events.unloads),
+		],
+	);
+	output
+}
+
+/// Return the current metrics of the running kernel.
+fn metrics(
+	events: &Mutex<ModuleEvents>,
+	messages: &Mutex<LogMessages>,
+) -> Result<String, Box<dyn Error>> {
+	let graph = ModuleGraph::loaded()?;
+	let events = *events.lock().map_err(|e| e.to_string())?;
+	let messages = *messages.lock().map_err(|e| e.to_string())?;
+	Ok(render_metrics(
+		&graph,
+		modinfo::builtin_modules(&modinfo::modules_dir()).len(),
+		fs::read_to_string("/proc/sys/kernel/tainted")
+			.ok()
+			.and_then(|v| v.trim().parse().ok())
+			.unwrap_or_default(),
+		&messages,
+		&events,
+	))
+}
+
+/// Respond to an HTTP request with the output of the given function.
+///
+/// Only the `/metrics` path is served.
+fn handle_request(
+	stream: TcpStream,
+	metrics: impl Fn() -> Result<String, Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+	// Clients that do not send a request are not waited forever.
+	stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+	let mut reader = BufReader::new(&stream);
+	let mut request = String::new();
+	reader.read_line(&mut request)?;
+	// Skip the request headers.
+	let mut header = String::new();
+	while reader.read_line(&mut header)? > 2 {
+		header.clear();
+	}
+	let (status, content_type, body) =
+		match request.split_whitespace().take(2).collect::<Vec<&str>>()[..] {
+			["GET", "/metrics"] => match metrics() {
+				Ok(body) => ("200 OK", CONTENT_TYPE, body),
+				Err(e) => {
+					("500 Internal Server Error", "text/plain", format!("{e}\n"))
+				}
+			},
+			_ => ("404 Not Found", "text/plain", String::from("not found\n")),
+		};
+	write!(
+		&stream,
+		"HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\n\
+		Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+		body.len()
+	)?;
+	Ok(())
+}
+
+/// Serve the metrics over HTTP for the `serve` subcommand.
+///
+/// Module events and log messages are counted between the scrapes as they
+/// are received, or by checking the loaded modules at the refresh rate.
+/// Each connection is handled in its own thread.
+pub fn serve(args: &ArgMatches, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+	let address = matches
+		.get_one::<String>("listen")
+		.ok_or("address is not given")?;
+	let refresh_rate = args
+		.try_get_one::<String>("rate")
+		.ok()
+		.flatten()
+		.and_then(|v| v.parse().ok())
+		.unwrap_or(250);
+	let listener = TcpListener::bind(address)?;
+	let events = Arc::new(Mutex::new(ModuleEvents::default()));
+	{
+		let events = Arc::clone(&events);
+		thread::spawn(move || {
+			uevent::watch(Duration::from_millis(refresh_rate), |event| {
+				if let Ok(mut events) = events.lock() {
+					events.add(&event);
+				}
+				true
+			})
+		});
+	}
+	let messages = Arc::new(Mutex::new(LogMessages::default()));
+	{
+		let messages = Arc::clone(&messages);
+		thread::spawn(move || {
+			let result = log::watch(true, |record| {
+				if let Ok(mut messages) = messages.lock() {
+					messages.add(record);
+				}
+				true
+			});
+			if let Err(e) = result {
+				eprintln!("{e}");
+			}
+		});
+	}
+	println!(
+		"Serving the metrics on http://{}/metrics",
+		listener.local_addr()?
+	);
+	for stream in listener.incoming() {
+		let stream = stream?;
+		let (events, messages) = (Arc::clone(&events), Arc::clone(&messages));
+		thread::spawn(move || {
+			if let Err(e) = handle_request(stream, || metrics(&events, &messages)) {
+				eprintln!("{e}");
+			}
+		});
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::Read;
+	#[test]
+	fn test_metrics() -> Result<(), Box<dyn Error>> {
+		let mut events = ModuleEvents::default();
+		for (action, module) in [
+			(ModuleAction::Load, "snd"),
+			(ModuleAction::Load, "snd_hda_intel"),
+			(ModuleAction::Unload, "a"),
+		] {
+			events.add(&ModuleEvent {
+				action,
+				module: module.to_string(),
+			});
+		}
+		assert_eq!((2, 1), (events.loads, events.unloads));
+		let graph = ModuleGraph::parse(
+			"b 1024 0 - Live 0x0\nsnd 2048 1 snd_hda_intel, Live 0x0\n\
+			snd_hda_intel 512 0 - Live 0x0\n",
+		);
+		let mut messages = LogMessages::default();
+		for line in [
+			"kern  :warn  : [0] a",
+			"kern  :warn  : [0] b",
+			"kern  :err   : [0] c",
+		] {
+			messages.add(&LogRecord::parse(line, None));
+		}
+		let metrics = render_metrics(&graph, 5, 4097, &messages, &events);
+		for line in [
+			"kmon_modules{state=\"loaded\"} 3",
+			"kmon_modules{state=\"builtin\"} 5",
+			"kmon_module_size_bytes{module=\"snd\"} 2048",
+			"kmon_module_refcount{module=\"snd\"} 1",
+			"kmon_kernel_tainted 4097",
+			"kmon_kernel_taint{bit=\"0\",flag=\"P\"} 1",
+			"kmon_kernel_taint{bit=\"1\",flag=\"F\"} 0",
+			"kmon_kernel_taint{bit=\"12\",flag=\"O\"} 1",
+			"kmon_kernel_log_messages_total{level=\"warn\"} 2",
+			"kmon_kernel_log_messages_total{level=\"err\"} 1",
+			"kmon_kernel_log_messages_total{level=\"info\"} 0",
+			"# TYPE kmon_kernel_log_messages_total counter",
+			"kmon_module_events_total{event=\"load\"} 2",
+			"# TYPE kmon_module_events_total counter",
+		] {
+			assert!(metrics.lines().any(|v| v == line), "{line}");
+		}
+		let listener = TcpListener::bind("127.0.0.1:0")?;
+		let address = listener.local_addr()?;
+		let server = thread::spawn(move || {
+			for _ in 0..2 {
+				if let Ok((stream, _)) = listener.accept() {
+					let _ = handle_request(stream, || Ok(String::from("kmon 1\n")));
+				}
+			}
+		});
+		let mut responses = Vec::new();
+		for path in ["/metrics", "/"] {
+			let mut stream = TcpStream::connect(address)?;
+			write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n")?;
+			let mut response = String::new();
+			stream.read_to_string(&mut response)?;
+			responses.push(response);
+		}
+		let _ = server.join();
+		assert!(responses[0].starts_with("HTTP/1.1 200 OK\r\n"));
+		assert!(responses[0].contains(CONTENT_TYPE));
+		assert!(responses[0].ends_with("\r\n\r\nkmon 1\n"));
+		assert!(responses[1].starts_with("HTTP/1.1 404 Not Found\r\n"));
+		Ok(())
+	}
+}
@@ -0,0 +1,341 @@
+use bytesize::ByteSize;
+use clap::ArgMatches;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt::{Display, Formatter, Write as _};
+use std::fs;
+
+/// Output format of the dependency graph
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GraphFormat {
+	#[default]
+	Dot,
+	Mermaid,
+	Json,
+}
+
+impl GraphFormat {
+	/// Return the next format.
+	pub fn next(self) -> Self {
+		match self {
+			Self::Dot => Self::Mermaid,
+			Self::Mermaid => Self::Json,
+			Self::Json => Self::Dot,
+		}
+	}
+}
+
+impl Display for GraphFormat {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", format!("{self:?}").to_lowercase())
+	}
+}
+
+impl TryFrom<&str> for GraphFormat {
+	type Error = String;
+	fn try_from(s: &str) -> Result<Self, Self::Error> {
+		match s.to_lowercase().as_str() {
+			"dot" | "graphviz" => Ok(Self::Dot),
+			"mermaid" => Ok(Self::Mermaid),
+			"json" => Ok(Self::Json),
+			_ => Err(format!("unknown graph format: {s}")),
+		}
+	}
+}
+
+/// Node of the dependency graph
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GraphNode {
+	pub size: u64,
+	pub refcount: u32,
+	pub taint: String,
+}
+
+impl GraphNode {
+	/// Return the label of the node.
+	fn label(&self, name: &str) -> String {
+		if self.taint.is_empty() {
+			format!("{name} ({})", ByteSize::b(self.size).to_string_as(true))
+		} else {
+			format!(
+				"{name} ({}, {})",
+				ByteSize::b(self.size).to_string_as(true),
+				self.taint
+			)
+		}
+	}
+}
+
+/// Dependency graph of the loaded kernel modules
+///
+/// An edge goes from a module to the module that it depends on.
+#[derive(Clone, Debug, Default)]
+pub struct ModuleGraph {
+	pub nodes: BTreeMap<String, GraphNode>,
+	pub edges: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl ModuleGraph {
+	/// Create the graph of the loaded modules from '/proc/modules'.
+	pub fn loaded() -> Result<Self, Box<dyn Error>> {
+		Ok(Self::parse(&fs::read_to_string("/proc/modules")?))
+	}
+
+	/// Parse the contents of '/proc/modules'.
+	pub fn parse(proc_modules: &str) -> Self {
+		let mut graph = Self::default();
+		for line in proc_modules.lines() {
+			let columns: Vec<&str> = line.split_whitespace().collect();
+			let Some(name) = columns.first() else {
+				continue;
+			};
+			let taint = columns
+				.last()
+				.filter(|v| v.starts_with('(') && v.ends_with(')'))
+				.map(|v| v.trim_matches(|c| c == '(' || c == ')'))
+				.unwrap_or_default();
+			graph.nodes.insert(
+				name.to_string(),
+				GraphNode {
+					size: columns
+						.get(1)
+						.and_then(|v| v.parse().ok())
+						.unwrap_or_default(),
+					refcount: columns
+						.get(2)
+						.and_then(|v| v.parse().ok())
+						.unwrap_or_default(),
+					taint: taint.to_string(),
+				},
+			);
+			graph.edges.entry(name.to_string()).or_default();
+			for holder in columns
+				.get(3)
+				.unwrap_or(&"-")
+				.split(',')
+				.filter(|v| !v.is_empty() && *v != "-")
+			{
+				graph
+					.edges
+					.entry(holder.to_string())
+					.or_default()
+					.insert(name.to_string());
+			}
+		}
+		graph
+	}
+
+	/// Return the modules that hold (depend on) the given module.
+	pub fn holders(&self, name: &str) -> Vec<&str> {
+		self.edges
+			.iter()
+			.filter(|(_, to)| to.contains(name))
+			.map(|(from, _)| from.as_str())
+			.collect()
+	}
+
+	/// Check if the module depends on the other module, directly or through
+	/// other modules.
+	pub fn depends_on(&self, name: &str, other: &str) -> bool {
+		let mut visited = BTreeSet::new();
+		let mut stack = vec![name];
+		while let Some(current) = stack.pop() {
+			for next in self.edges.get(current).into_iter().flatten() {
+				if next == other {
+					return true;
+				}
+				if visited.insert(next.as_str()) {
+					stack.push(next);
+				}
+			}
+		}
+		false
+	}
+
+	/// Sort the modules in the order of removal.
+	///
+	/// Holders come before the modules that they hold, otherwise the given
+	/// order is kept.
+	pub fn removal_order(&self, names: &[String]) -> Vec<String> {
+		let mut remaining = names.to_vec();
+		let mut order = Vec::new();
+		while !remaining.is_empty() {
+			let index = remaining
+				.iter()
+				.position(|name| {
+					!remaining
+						.iter()
+						.any(|other| other != name && self.depends_on(other, name))
+				})
+				.unwrap_or_default();
+			order.push(remaining.remove(index));
+		}
+		order
+	}
+
+	/// Return the subgraph that contains the given module, the modules it
+	/// depends on and the modules that depend on it, recursively.
+	pub fn subgraph(&self, root: &str) -> Self {
+		let mut names = BTreeSet::from([root.to_string()]);
+		for reverse in [false, true] {
+			let mut stack = vec![root.to_string()];
+			while let Some(name) = stack.pop() {
+				for (from, to) in self
+					.edges
+					.iter()
+					.flat_map(|(from, to)| to.iter().map(move |to| (from, to)))
+				{
+					let (current, next) =
+						if reverse { (to, from) } else { (from, to) };
+					if *current == name && names.insert(next.to_string()) {
+						stack.push(next.to_string());
+					}
+				}
+			}
+		}
+		Self {
+			nodes: self
+				.nodes
+				.iter()
+				.filter(|(name, _)| names.contains(*name))
+				.map(|(name, node)| (name.to_string(), node.clone()))
+				.collect(),
+			edges: self
+				.edges
+				.iter()
+				.filter(|(name, _)| names.contains(*name))
+				.map(|(name, to)| {
+					(
+						name.to_string(),
+						to.iter().filter(|v| names.contains(*v)).cloned().collect(),
+					)
+				})
+				.collect(),
+		}
+	}
+
+	/// Export the graph in the given format.
+	pub fn export(&self, format: GraphFormat) -> String {
+		let mut output = String::new();
+		match format {
+			GraphFormat::Dot => {
+				output += "digraph modules {\n\tnode [shape=box];\n";
+				for (name, node) in &self.nodes {
+					let _ = writeln!(
+						output,
+						"\t\"{name}\" [label=\"{}\", size_bytes={}, taint=\"{}\"];",
+						node.label(name),
+						node.size,
+						node.taint
+					);
+				}
+				for (from, to) in &self.edges {
+					for to in to {
+						let _ = writeln!(output, "\t\"{from}\" -> \"{to}\";");
+					}
+				}
+				output += "}";
+			}
+			GraphFormat::Mermaid => {
+				output += "graph TD\n";
+				for (name, node) in &self.nodes {
+					let _ = writeln!(output, "\t{name}[\"{}\"]", node.label(name));
+				}
+				for (from, to) in &self.edges {
+					for to in to {
+						let _ = writeln!(output, "\t{from} --> {to}");
+					}
+				}
+				output = output.trim_end().to_string();
+			}
+			GraphFormat::Json => {
+				let nodes = self
+					.nodes
+					.iter()
+					.map(|(name, node)| {
+						(
+							name.to_string(),
+							serde_json::json!({
+								"size": node.size,
+								"refcount": node.refcount,
+								"taint": node.taint,
+								"depends": self.edges.get(name).cloned().unwrap_or_default(),
+							}),
+						)
+					})
+					.collect::<serde_json::Map<String, serde_json::Value>>();
+				output = serde_json::to_string_pretty(&nodes).unwrap_or_default();
+			}
+		}
+		output
+	}
+}
+
+/// Print the graph of the loaded modules for the `graph` subcommand.
+///
+/// Graph is limited to the modules that are connected to the root module
+/// if it is given.
+pub fn run(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+	let mut graph = ModuleGraph::loaded()?;
+	if let Some(root) = matches.get_one::<String>("root") {
+		if !graph.nodes.contains_key(root) {
+			return Err(format!("module is not loaded: {root}").into());
+		}
+		graph = graph.subgraph(root);
+	}
+	let format = matches
+		.get_one::<String>("format")
+		.map(String::as_str)
+		.unwrap_or_default();
+	println!("{}", graph.export(GraphFormat::try_from(format)?));
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	#[test]
+	fn test_module_graph() {
+		let graph = ModuleGraph::parse(
+			"nvidia_drm 77824 4 - Live 0x0 (POE)\n\
+			nvidia 1024 5 nvidia_drm, Live 0x0 (POE)\n\
+			snd_hda_intel 61440 3 - Live 0x0\n\
+			snd 135168 12 snd_hda_intel, Live 0x0\n",
+		);
+		assert_eq!(4, graph.nodes.len());
+		assert_eq!("POE", graph.nodes["nvidia"].taint);
+		assert_eq!(12, graph.nodes["snd"].refcount);
+		assert_eq!(
+			BTreeSet::from([String::from("snd")]),
+			graph.edges["snd_hda_intel"]
+		);
+		assert_eq!(vec!["nvidia_drm"], graph.holders("nvidia"));
+		assert!(graph.depends_on("snd_hda_intel", "snd"));
+		assert!(!graph.depends_on("snd", "snd_hda_intel"));
+		assert_eq!(
+			vec!["snd_hda_intel", "snd", "nvidia_drm", "nvidia", "~"],
+			graph.removal_order(
+				&["snd", "nvidia", "snd_hda_intel", "nvidia_drm", "~"]
+					.map(String::from)
+			)
+		);
+		let subgraph = graph.subgraph("snd");
+		assert_eq!(
+			vec!["snd", "snd_hda_intel"],
+			subgraph.nodes.keys().collect::<Vec<_>>()
+		);
+		assert!(subgraph
+			.export(GraphFormat::Dot)
+			.contains("\t\"snd_hda_intel\" -> \"snd\";\n"));
+		assert!(subgraph
+			.export(GraphFormat::Mermaid)
+			.ends_with("\tsnd_hda_intel --> snd"));
+		let json: serde_json::Value =
+			serde_json::from_str(&subgraph.export(GraphFormat::Json)).unwrap();
+		assert_eq!(61440, json["snd_hda_intel"]["size"]);
+		assert_eq!("snd", json["snd_hda_intel"]["depends"][0]);
+		assert_eq!(GraphFormat::Dot, GraphFormat::Json.next());
+		assert_eq!("mermaid", GraphFormat::Mermaid.to_string());
+		assert_eq!(Ok(GraphFormat::Mermaid), GraphFormat::try_from("mermaid"));
+	}
+}
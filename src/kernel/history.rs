@@ -0,0 +1,212 @@
+use crate::kernel::cmd::ModuleCommand;
+use crate::kernel::modprobe::{self, FileWrite};
+use crate::style::Style;
+use crate::util;
+use ratatui::text::{Line, Span, Text};
+use std::fs;
+use std::path::PathBuf;
+
+/// Operation that is executed on a kernel module
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Operation {
+	pub timestamp: u64,
+	pub command: ModuleCommand,
+	pub module: String,
+	pub parameters: String,
+	pub added_lines: Vec<(PathBuf, Vec<String>)>,
+	pub undone: bool,
+}
+
+impl Operation {
+	/// Create a new operation before the command is executed.
+	///
+	/// Parameters of the module and the lines that the writes of the command
+	/// add to the configuration files are captured for undoing the operation.
+	pub fn new(command: ModuleCommand, module: &str, writes: &[FileWrite]) -> Self {
+		Self {
+			timestamp: util::timestamp(),
+			command,
+			module: module.to_string(),
+			parameters: if matches!(
+				command,
+				ModuleCommand::Unload | ModuleCommand::Reload
+			) {
+				ModuleCommand::runtime_options(module)
+			} else {
+				String::new()
+			},
+			added_lines: writes
+				.iter()
+				.map(|write| {
+					let lines = modprobe::added_lines(
+						&fs::read_to_string(&write.path).unwrap_or_default(),
+						&write.contents,
+					);
+					(write.path.to_path_buf(), lines)
+				})
+				.filter(|(_, lines)| !lines.is_empty())
+				.collect(),
+			undone: false,
+		}
+	}
+
+	/// Check if the operation can be undone.
+	pub fn is_undoable(&self) -> bool {
+		!self.undone
+			&& matches!(
+				self.command,
+				ModuleCommand::Unload
+					| ModuleCommand::Reload
+					| ModuleCommand::Blacklist
+			)
+	}
+
+	/// Return the command that reverses the operation.
+	pub fn undo_command(&self) -> Option<String> {
+		if !self.is_undoable() {
+			return None;
+		}
+		let load_command =
+			ModuleCommand::load_command(&self.module, &self.parameters);
+		match self.command {
+			ModuleCommand::Unload => Some(load_command),
+			ModuleCommand::Reload => Some(format!(
+				"{} && {load_command}",
+				ModuleCommand::Unload.get(&self.module).cmd
+			)),
+			_ if !self.added_lines.is_empty() => {
+				Some(modprobe::write_commands(&self.undo_writes()))
+			}
+			_ => None,
+		}
+	}
+
+	/// Return the writes that remove the lines added by the operation.
+	pub fn undo_writes(&self) -> Vec<FileWrite> {
+		if !self.is_undoable() {
+			return Vec::new();
+		}
+		self.added_lines
+			.iter()
+			.map(|(path, lines)| modprobe::remove_lines_write(path, lines))
+			.collect()
+	}
+}
+
+/// History of the operations that are executed in the current session
+#[derive(Clone, Debug, Default)]
+pub struct History {
+	pub operations: Vec<Operation>,
+}
+
+impl History {
+	/// Add an executed operation to the history.
+	///
+	/// Undone operation is marked if the operation is an undo.
+	pub fn push(&mut self, operation: Operation) {
+		if operation.command == ModuleCommand::Undo {
+			if let Some(undone) =
+				self.operations.iter_mut().rev().find(|v| v.is_undoable())
+			{
+				undone.undone = true;
+			}
+		}
+		self.operations.push(operation);
+	}
+
+	/// Return the last operation that can be undone.
+	pub fn last_undoable(&self) -> Option<&Operation> {
+		self.operations.iter().rev().find(|v| v.is_undoable())
+	}
+
+	/// Render the operations as styled and raw text.
+	///
+	/// Operations are listed from the newest to the oldest.
+	pub fn render(&self, style: &Style) -> (Text<'static>, String) {
+		let mut lines = Vec::new();
+		let mut raw_lines = Vec::new();
+		if self.operations.is_empty() {
+			let message = String::from("no operations are executed");
+			lines.push(Line::from(Span::styled(message.to_string(), style.colored)));
+			raw_lines.push(message);
+		}
+		for operation in self.operations.iter().rev() {
+			let command = operation.command.name();
+			let mut details = Vec::new();
+			if !operation.parameters.is_empty() {
+				details.push(operation.parameters.to_string());
+			}
+			if operation.undone {
+				details.push(String::from("undone"));
+			} else if operation.is_undoable() {
+				details.push(String::from("undoable"));
+			}
+			let details = if details.is_empty() {
+				String::new()
+			} else {
+				format!(" ({})", details.join(", "))
+			};
+			let time = util::format_timestamp(operation.timestamp);
+			lines.push(Line::from(vec![
+				Span::styled(format!("{time} "), style.colored),
+				Span::styled(format!("{command} "), style.bold),
+				Span::styled(operation.module.to_string(), style.default),
+				Span::styled(details.to_string(), style.colored),
+			]));
+			raw_lines
+				.push(format!("{time} {command} {}{details}", operation.module));
+		}
+		(Text::from(lines), raw_lines.join("\n"))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use clap::ArgMatches;
+	use std::path::Path;
+	#[test]
+	fn test_history() {
+		let mut history = History::default();
+		history.push(Operation::new(ModuleCommand::Load, "~", &[]));
+		assert_eq!(None, history.last_undoable());
+		history.push(Operation {
+			command: ModuleCommand::Unload,
+			module: String::from("snd"),
+			parameters: String::from("index=0"),
+			..Operation::default()
+		});
+		history.push(Operation {
+			command: ModuleCommand::Blacklist,
+			module: String::from("snd"),
+			added_lines: vec![(
+				PathBuf::from("/tmp/~.conf"),
+				vec![String::from("blacklist snd")],
+			)],
+			..Operation::default()
+		});
+		assert!(history
+			.last_undoable()
+			.and_then(|v| v.undo_command())
+			.is_some_and(
+				|v| v.ends_with("'/tmp/~.conf'") && !v.contains("blacklist snd")
+			));
+		assert!(history.last_undoable().is_some_and(|v| v.undo_writes()
+			== [FileWrite::new(Path::new("/tmp/~.conf"), String::new())]));
+		history.push(Operation::new(ModuleCommand::Undo, "snd", &[]));
+		assert_eq!(
+			Some(String::from(
+				"modprobe snd 'index=0' || insmod snd.ko 'index=0'"
+			)),
+			history.last_undoable().and_then(|v| v.undo_command())
+		);
+		let raw_text = history.render(&Style::new(&ArgMatches::default())).1;
+		let lines = raw_text.lines().collect::<Vec<&str>>();
+		assert!(lines[0].ends_with(" undo snd"));
+		assert!(lines[1].ends_with(" blacklist snd (undone)"));
+		assert_eq!(
+			"1970-01-01T00:00:00Z unload snd (index=0, undoable)",
+			lines[2]
+		);
+	}
+}
@@ -0,0 +1,188 @@
+use crate::kernel::graph::ModuleGraph;
+use crate::kernel::sysfs;
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+/// Classes of the interfaces that are shown in the impact
+const INTERFACE_CLASSES: &[(&str, &str)] =
+	&[("net", "network"), ("block", "block"), ("sound", "sound")];
+
+/// Impact of unloading a kernel module
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnloadImpact {
+	pub refcount: u32,
+	pub modules: Vec<String>,
+	pub devices: Vec<(String, String)>,
+	pub interfaces: Vec<(String, String)>,
+}
+
+impl UnloadImpact {
+	/// Create a new unload impact for the given module.
+	pub fn new(graph: &ModuleGraph, sys_module_dir: &Path, name: &str) -> Self {
+		let mut impact = Self {
+			refcount: graph
+				.nodes
+				.get(name)
+				.map(|v| v.refcount)
+				.unwrap_or_default(),
+			modules: Self::removed_modules(graph, name),
+			..Self::default()
+		};
+		for module in [name.to_string()].iter().chain(&impact.modules) {
+			for (driver, device) in sysfs::module_devices(sys_module_dir, module) {
+				impact.interfaces.extend(sysfs::device_interfaces(&device));
+				impact.devices.push((
+					device
+						.file_name()
+						.map(|v| v.to_string_lossy().to_string())
+						.unwrap_or_default(),
+					driver,
+				));
+			}
+		}
+		impact.devices.sort();
+		impact.devices.dedup();
+		impact.interfaces.sort();
+		impact.interfaces.dedup();
+		impact
+	}
+
+	/// Return the dependencies that are removed with the module.
+	///
+	/// Dependencies are removed by 'modprobe -r' if they are not used by
+	/// anything other than the removed modules.
+	fn removed_modules(graph: &ModuleGraph, name: &str) -> Vec<String> {
+		let mut removed = BTreeSet::from([name]);
+		let mut changed = true;
+		while changed {
+			changed = false;
+			let dependencies = removed
+				.iter()
+				.flat_map(|v| graph.edges.get(*v).into_iter().flatten())
+				.map(String::as_str)
+				.filter(|v| !removed.contains(v))
+				.collect::<BTreeSet<&str>>();
+			for dependency in dependencies {
+				let holders = graph.holders(dependency);
+				let refcount = graph
+					.nodes
+					.get(dependency)
+					.map(|v| v.refcount)
+					.unwrap_or_default();
+				if holders.iter().all(|v| removed.contains(v))
+					&& refcount as usize <= holders.len()
+				{
+					removed.insert(dependency);
+					changed = true;
+				}
+			}
+		}
+		removed.remove(name);
+		removed.into_iter().map(String::from).collect()
+	}
+
+	/// Check if unloading the module will fail.
+	pub fn is_in_use(&self) -> bool {
+		self.refcount != 0
+	}
+}
+
+impl Display for UnloadImpact {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		let join = |values: Vec<String>| {
+			if values.is_empty() {
+				String::from("-")
+			} else {
+				values.join(", ")
+			}
+		};
+		writeln!(
+			f,
+			"refcount: {}{}",
+			self.refcount,
+			if self.is_in_use() {
+				" (the module is in use, unloading will fail)"
+			} else {
+				""
+			}
+		)?;
+		writeln!(f, "removed dependencies: {}", join(self.modules.clone()))?;
+		write!(
+			f,
+			"bound devices: {}",
+			join(
+				self.devices
+					.iter()
+					.map(|(device, driver)| format!("{device} ({driver})"))
+					.collect()
+			)
+		)?;
+		for (class, title) in INTERFACE_CLASSES {
+			write!(
+				f,
+				"\n{title}: {}",
+				join(
+					self.interfaces
+						.iter()
+						.filter(|(v, _)| v == class)
+						.map(|(_, name)| name.to_string())
+						.collect()
+				)
+			)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::error::Error;
+	use std::fs;
+	use std::os::unix::fs::symlink;
+	#[test]
+	fn test_unload_impact() -> Result<(), Box<dyn Error>> {
+		let dir = crate::util::temp_path("impact");
+		let device = dir.join("devices/pci0000:00/0000:00:1f.6");
+		let driver = dir.join("bus/pci/drivers/e1000e");
+		fs::create_dir_all(device.join("net/eno1"))?;
+		fs::create_dir_all(&driver)?;
+		fs::create_dir_all(dir.join("module/e1000e/drivers"))?;
+		fs::write(driver.join("bind"), "")?;
+		symlink(&device, driver.join("0000:00:1f.6"))?;
+		symlink(dir.join("module/e1000e"), driver.join("module"))?;
+		symlink(&driver, dir.join("module/e1000e/drivers/pci:e1000e"))?;
+		let graph = ModuleGraph::parse(
+			"e1000e 1024 0 - Live 0x0\n\
+			ptp 1024 2 e1000e,igb, Live 0x0\n\
+			igb 1024 0 - Live 0x0\n\
+			pps_core 1024 1 ptp, Live 0x0\n\
+			crc32 1024 1 e1000e, Live 0x0\n",
+		);
+		let impact = UnloadImpact::new(&graph, &dir.join("module"), "e1000e");
+		fs::remove_dir_all(dir)?;
+		assert_eq!(vec![String::from("crc32")], impact.modules);
+		assert_eq!(
+			vec![(String::from("0000:00:1f.6"), String::from("pci:e1000e"))],
+			impact.devices
+		);
+		assert_eq!(
+			vec![(String::from("net"), String::from("eno1"))],
+			impact.interfaces
+		);
+		assert!(!impact.is_in_use());
+		assert_eq!(
+			"refcount: 0\nremoved dependencies: crc32\n\
+			bound devices: 0000:00:1f.6 (pci:e1000e)\n\
+			network: eno1\nblock: -\nsound: -",
+			impact.to_string()
+		);
+		assert!(UnloadImpact::removed_modules(&graph, "igb").is_empty());
+		assert_eq!(
+			vec![String::from("pps_core")],
+			UnloadImpact::removed_modules(&graph, "ptp")
+		);
+		Ok(())
+	}
+}
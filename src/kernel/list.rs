@@ -0,0 +1,201 @@
+use crate::kernel::lkm::{KernelModules, ListArgs};
+use crate::style::Style;
+use clap::ArgMatches;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Write as _};
+
+/// Output format of the module list
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ListFormat {
+	#[default]
+	Table,
+	Csv,
+	Json,
+}
+
+impl Display for ListFormat {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", format!("{self:?}").to_lowercase())
+	}
+}
+
+impl TryFrom<&str> for ListFormat {
+	type Error = String;
+	fn try_from(s: &str) -> Result<Self, Self::Error> {
+		match s.to_lowercase().as_str() {
+			"table" => Ok(Self::Table),
+			"csv" => Ok(Self::Csv),
+			"json" => Ok(Self::Json),
+			_ => Err(format!("unknown list format: {s}")),
+		}
+	}
+}
+
+/// Entry of the module list
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ListEntry {
+	pub name: String,
+	pub flags: Vec<String>,
+	pub size: String,
+	pub used: u64,
+	pub used_by: Vec<String>,
+}
+
+impl From<&Vec<String>> for ListEntry {
+	/// Create an entry from a row of the module table.
+	///
+	/// Taint flags and markers that follow the module name are
+	/// returned as flags.
+	fn from(row: &Vec<String>) -> Self {
+		let mut name = row[0].split_whitespace().map(String::from);
+		let (used, used_by) = row[2].split_once(' ').unwrap_or((&row[2], "-"));
+		Self {
+			name: name.next().unwrap_or_default(),
+			flags: name.collect(),
+			size: row[1].to_string(),
+			used: used.parse().unwrap_or_default(),
+			used_by: used_by
+				.split(',')
+				.filter(|v| !v.is_empty() && *v != "-")
+				.map(String::from)
+				.collect(),
+		}
+	}
+}
+
+/// Quote a field of a CSV record if needed.
+fn csv_field(field: &str) -> String {
+	if field.contains([',', '"', '\n']) {
+		format!("\"{}\"", field.replace('"', "\"\""))
+	} else {
+		field.to_string()
+	}
+}
+
+/// Return the rows of the module table in the given format.
+pub fn format_list(module_list: &[Vec<String>], format: ListFormat) -> String {
+	let entries = module_list.iter().map(ListEntry::from);
+	match format {
+		ListFormat::Table => {
+			let name_width = module_list
+				.iter()
+				.map(|row| row[0].trim().len())
+				.chain([6])
+				.max()
+				.unwrap_or_default();
+			let size_width = module_list
+				.iter()
+				.map(|row| row[1].len())
+				.chain([4])
+				.max()
+				.unwrap_or_default();
+			let mut table = format!(
+				"{:<name_width$}  {:<size_width$}  Used by",
+				"Module", "Size"
+			);
+			for row in module_list {
+				let _ = write!(
+					table,
+					"\n{:<name_width$}  {:<size_width$}  {}",
+					row[0].trim(),
+					row[1],
+					row[2]
+				);
+			}
+			table
+		}
+		ListFormat::Csv => {
+			let mut csv = String::from("name,flags,size,used,used_by");
+			for entry in entries {
+				let _ = write!(
+					csv,
+					"\n{},{},{},{},{}",
+					csv_field(&entry.name),
+					csv_field(&entry.flags.join(" ")),
+					csv_field(&entry.size),
+					entry.used,
+					csv_field(&entry.used_by.join(","))
+				);
+			}
+			csv
+		}
+		ListFormat::Json => serde_json::Value::Array(
+			entries
+				.map(|entry| {
+					serde_json::json!({
+						"name": entry.name,
+						"flags": entry.flags,
+						"size": entry.size,
+						"used": entry.used,
+						"used_by": entry.used_by,
+					})
+				})
+				.collect(),
+		)
+		.to_string(),
+	}
+}
+
+/// Print the loaded modules for the `list` subcommand.
+///
+/// Modules are sorted and searched in the same way as the module table.
+pub fn run(args: &ArgMatches, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+	let format = ListFormat::try_from(
+		matches
+			.get_one::<String>("format")
+			.map(String::as_str)
+			.unwrap_or_default(),
+	)?;
+	let kernel_modules = KernelModules::load(ListArgs::new(args), Style::new(args))?;
+	let mut module_list = kernel_modules.default_list;
+	if let Some(query) = matches.get_one::<String>("filter") {
+		module_list = kernel_modules
+			.args
+			.search_mode()
+			.filter(module_list, query)
+			.0;
+	}
+	println!("{}", format_list(&module_list, format));
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	#[test]
+	fn test_format_list() {
+		let module_list = vec![
+			vec![
+				String::from(" nvidia (POE)"),
+				String::from("56.3 MiB"),
+				String::from("2 nvidia_drm,nvidia_modeset"),
+			],
+			vec![
+				String::from(" ext4 [built-in]"),
+				String::from("-"),
+				String::from("-"),
+			],
+		];
+		assert_eq!(Ok(ListFormat::Csv), ListFormat::try_from("CSV"));
+		assert!(ListFormat::try_from("xml").is_err());
+		assert_eq!(
+			"Module           Size      Used by\n\
+			nvidia (POE)     56.3 MiB  2 nvidia_drm,nvidia_modeset\n\
+			ext4 [built-in]  -         -",
+			format_list(&module_list, ListFormat::Table)
+		);
+		assert_eq!(
+			"name,flags,size,used,used_by\n\
+			nvidia,(POE),56.3 MiB,2,\"nvidia_drm,nvidia_modeset\"\n\
+			ext4,[built-in],-,0,",
+			format_list(&module_list, ListFormat::Csv)
+		);
+		let json: serde_json::Value =
+			serde_json::from_str(&format_list(&module_list, ListFormat::Json))
+				.unwrap_or_default();
+		assert_eq!("nvidia", json[0]["name"]);
+		assert_eq!(2, json[0]["used"]);
+		assert_eq!("nvidia_modeset", json[0]["used_by"][1]);
+		assert_eq!("[built-in]", json[1]["flags"][0]);
+	}
+}
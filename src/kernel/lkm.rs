@@ -1,12 +1,54 @@
 use crate::app::ScrollDirection;
-use crate::kernel::cmd::{Command, ModuleCommand};
+use crate::kernel::audit::{AuditLog, AuditRecord};
+use crate::kernel::autoload;
+use crate::kernel::cmd::{Command, Escalation, ModuleCommand, ProtectedModules};
+use crate::kernel::deps::DependencyTree;
+use crate::kernel::graph::{GraphFormat, ModuleGraph};
+use crate::kernel::history::{History, Operation};
+use crate::kernel::impact::UnloadImpact;
+use crate::kernel::modinfo::{self, ModuleInfo};
+use crate::kernel::modprobe::{self, FileWrite};
+use crate::kernel::params::OptionsEditor;
+use crate::kernel::snapshot::{ChangeKind, ModuleChange, Snapshot, REMOVED_MARKER};
+use crate::kernel::sysfs;
+use crate::kernel::uevent::{ModuleAction, ModuleEvent};
 use crate::style::{Style, StyledText, Symbol};
 use crate::util;
 use bytesize::ByteSize;
 use clap::ArgMatches;
+use enum_iterator::Sequence;
 use ratatui::text::{Line, Span, Text};
+use regex_lite::RegexBuilder;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::fmt::{Display, Formatter, Write as _};
+use std::fs;
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process::{self, Output};
 use std::slice::Iter;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Placeholder text that is shown while the module information is loading
+const LOADING_PLACEHOLDER: &str = "loading\u{2026}";
+
+/// Marker of the built-in modules in the module table
+pub const BUILTIN_MARKER: &str = "[built-in]";
+
+/// Marker of the modules that are configured at boot by kmon
+pub const PERSISTENT_MARKER: &str = "[boot]";
+
+/// Marker of the modules that are loaded while kmon is running
+pub const LOADED_MARKER: &str = "[loaded]";
+
+/// Marker of the modules that are unloaded while kmon is running
+pub const UNLOADED_MARKER: &str = "[unloaded]";
+
+/// Duration of highlighting the modules that are loaded or unloaded
+const EVENT_HIGHLIGHT_DURATION: Duration = Duration::from_secs(3);
 
 /// Type of the sorting of module list
 #[derive(Clone, Copy, Debug)]
@@ -31,86 +73,550 @@ impl SortType {
 	}
 }
 
+/// Strategy of matching the search query with module names
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Sequence)]
+pub enum SearchMode {
+	Substring,
+	Regex,
+	Fuzzy,
+}
+
+impl SearchMode {
+	/// Filter the module list with the search query.
+	///
+	/// Returns the matching modules along with the character indices that
+	/// are matched by the fuzzy search.
+	pub fn filter(
+		self,
+		mut module_list: Vec<Vec<String>>,
+		query: &str,
+	) -> (Vec<Vec<String>>, Vec<Vec<usize>>) {
+		let mut matched_indices = Vec::new();
+		match self {
+			Self::Regex => {
+				if let Ok(regex) =
+					RegexBuilder::new(query).case_insensitive(true).build()
+				{
+					module_list
+						.retain(|module| regex.is_match(module[0].trim_start()))
+				}
+			}
+			Self::Fuzzy => {
+				// Rank the matching modules by their scores.
+				let mut ranked_list = module_list
+					.into_iter()
+					.filter_map(|module| {
+						util::fuzzy_match(
+							query,
+							module[0].split_whitespace().next()?,
+						)
+						.map(|(score, indices)| (score, indices, module))
+					})
+					.collect::<Vec<_>>();
+				ranked_list.sort_by_key(|(score, _, _)| std::cmp::Reverse(*score));
+				(matched_indices, module_list) = ranked_list
+					.into_iter()
+					.map(|(_, indices, module)| (indices, module))
+					.unzip();
+			}
+			Self::Substring => {
+				let query = query.to_lowercase();
+				module_list
+					.retain(|module| module[0].to_lowercase().contains(&query));
+			}
+		}
+		(module_list, matched_indices)
+	}
+}
+
+/// Implementation of Display for showing the search mode in input block
+impl Display for SearchMode {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", format!("{self:?}").to_lowercase())
+	}
+}
+
+/// View of the module table
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Sequence)]
+pub enum ModuleView {
+	Loaded,
+	Available,
+	Config,
+}
+
+/// Implementation of Display for showing the view in table title
+impl Display for ModuleView {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Config => write!(f, "Configured"),
+			_ => write!(f, "{self:?}"),
+		}
+	}
+}
+
 /// Listing properties of module list
 pub struct ListArgs {
 	sort: SortType,
 	reverse: bool,
-	regex: bool,
+	search: SearchMode,
 }
 
 impl ListArgs {
 	/// Create a new list arguments instance.
 	pub fn new(args: &ArgMatches) -> Self {
 		let mut sort_type = SortType::None;
-		if let Some(("sort", matches)) = args.subcommand() {
-			if matches.get_flag("size") {
-				sort_type = SortType::Size;
-			} else if matches.get_flag("dependent") {
-				sort_type = SortType::Dependent;
-			} else {
-				sort_type = SortType::Name;
+		match args.subcommand() {
+			Some(("sort", matches)) => {
+				if matches.get_flag("size") {
+					sort_type = SortType::Size;
+				} else if matches.get_flag("dependent") {
+					sort_type = SortType::Dependent;
+				} else {
+					sort_type = SortType::Name;
+				}
+			}
+			Some(("list", matches)) => {
+				sort_type =
+					match matches.get_one::<String>("sort").map(String::as_str) {
+						Some("size") => SortType::Size,
+						Some("name") => SortType::Name,
+						Some("dependent") => SortType::Dependent,
+						_ => SortType::None,
+					};
 			}
+			_ => {}
 		}
 		Self {
 			sort: sort_type,
 			reverse: args.try_get_one::<bool>("reverse").ok().flatten()
 				== Some(&true),
-			regex: args.try_get_one::<bool>("regex").ok().flatten() == Some(&true),
+			search: if args.try_get_one::<bool>("regex").ok().flatten()
+				== Some(&true)
+			{
+				SearchMode::Regex
+			} else if args.try_get_one::<bool>("fuzzy").ok().flatten() == Some(&true)
+			{
+				SearchMode::Fuzzy
+			} else {
+				SearchMode::Substring
+			},
+		}
+	}
+
+	/// Return the current search mode.
+	pub fn search_mode(&self) -> SearchMode {
+		self.search
+	}
+
+	/// Switch to the next search mode.
+	pub fn next_search_mode(&mut self) {
+		self.search = self.search.next().unwrap_or(SearchMode::Substring);
+	}
+}
+
+/// Cache of module information that is fetched on a background worker
+pub struct ModuleInfoCache {
+	cache: HashMap<String, String>,
+	pending: HashMap<String, usize>,
+	request_id: usize,
+	request_tx: mpsc::Sender<(usize, String)>,
+	response_rx: mpsc::Receiver<(usize, String, String)>,
+}
+
+impl Default for ModuleInfoCache {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl ModuleInfoCache {
+	/// Create a new module information cache and spawn its worker.
+	pub fn new() -> Self {
+		let (request_tx, request_rx) = mpsc::channel::<(usize, String)>();
+		let (response_tx, response_rx) = mpsc::channel();
+		thread::spawn(move || {
+			for (request_id, module_name) in request_rx {
+				let module_info = Self::fetch(&module_name);
+				if response_tx
+					.send((request_id, module_name, module_info))
+					.is_err()
+				{
+					break;
+				}
+			}
+		});
+		Self {
+			cache: HashMap::new(),
+			pending: HashMap::new(),
+			request_id: 0,
+			request_tx,
+			response_rx,
+		}
+	}
+
+	/// Read the module information from the module files.
+	///
+	/// Falls back to 'modinfo' if the module files cannot be read.
+	fn fetch(module_name: &str) -> String {
+		let mut module_info = ModuleInfo::read(module_name)
+			.map(|module_info| module_info.to_string())
+			.or_else(|_| util::exec_cmd("modinfo", &[module_name]))
+			.unwrap_or_else(|e| format!("module information not available: {e}"))
+			.replace("signature: ", "signature: \n");
+		// Append the current values of the module parameters.
+		for (param, value) in
+			sysfs::module_parameters(Path::new(sysfs::SYS_MODULE_DIR), module_name)
+		{
+			let _ = write!(module_info, "\n{:<16}{param}={value}", "parameter:");
+		}
+		module_info
+	}
+
+	/// Get the cached information or request it from the worker.
+	pub fn get(&mut self, module_name: &str) -> Option<&str> {
+		if !self.cache.contains_key(module_name)
+			&& !self.pending.contains_key(module_name)
+		{
+			self.request_id += 1;
+			self.pending
+				.insert(module_name.to_string(), self.request_id);
+			let _ = self
+				.request_tx
+				.send((self.request_id, module_name.to_string()));
+		}
+		self.cache.get(module_name).map(String::as_str)
+	}
+
+	/// Store the information received from the worker.
+	///
+	/// Returns the names of the modules that are received.
+	pub fn receive(&mut self) -> Vec<String> {
+		let mut received = Vec::new();
+		while let Ok((request_id, module_name, module_info)) =
+			self.response_rx.try_recv()
+		{
+			// Discard the responses of the requests sent before invalidation.
+			if self.pending.get(&module_name) == Some(&request_id) {
+				self.pending.remove(&module_name);
+				self.cache.insert(module_name.clone(), module_info);
+				received.push(module_name);
+			}
 		}
+		received
+	}
+
+	/// Invalidate the cached information.
+	pub fn clear(&mut self) {
+		self.cache.clear();
+		self.pending.clear();
 	}
 
-	pub fn regex(&self) -> bool {
-		self.regex
+	/// Invalidate the cached information of a module.
+	pub fn remove(&mut self, module_name: &str) {
+		self.cache.remove(module_name);
+		self.pending.remove(module_name);
 	}
 }
 
 /// Loadable kernel modules
-pub struct KernelModules<'a> {
+pub struct KernelModules {
 	pub default_list: Vec<Vec<String>>,
 	pub list: Vec<Vec<String>>,
 	pub current_name: String,
-	pub current_info: StyledText<'a>,
+	pub current_info: StyledText,
+	pub info_cache: ModuleInfoCache,
 	pub command: ModuleCommand,
+	pub command_generation: usize,
+	pub protected: ProtectedModules,
+	pub confirmation: Option<String>,
+	pub dry_run: bool,
+	pub read_only: bool,
+	pub escalation: Escalation,
+	pub audit_log: AuditLog,
+	pub suspended: bool,
+	pub dry_run_log: Vec<String>,
+	pub history: History,
+	pub marked: Vec<String>,
+	pub batch: Vec<String>,
+	pub snapshot: Option<Snapshot>,
+	pub snapshot_changes: HashMap<String, ModuleChange>,
+	pub module_events: Vec<(ModuleEvent, Instant)>,
+	pub pending_selection: Option<String>,
+	pub audit_error: Option<(String, String)>,
 	pub index: usize,
 	pub info_scroll_offset: usize,
+	pub dependency_tree: Option<DependencyTree>,
+	pub options_editor: Option<OptionsEditor>,
+	pub graph_format: GraphFormat,
+	pub view: ModuleView,
+	pub builtin_modules: Vec<String>,
 	pub style: Style,
 	pub args: ListArgs,
 }
 
-impl KernelModules<'_> {
+impl KernelModules {
 	/// Create a new kernel modules instance.
 	pub fn new(args: ListArgs, style: Style) -> Self {
-		let mut kernel_modules = Self {
+		let mut kernel_modules = Self::empty(args, style);
+		if let Err(e) = kernel_modules.refresh() {
+			eprintln!("{e}");
+		}
+		kernel_modules
+	}
+
+	/// Create a new kernel modules instance and return the error if the
+	/// module list cannot be read.
+	pub fn load(args: ListArgs, style: Style) -> Result<Self, Box<dyn Error>> {
+		let mut kernel_modules = Self::empty(args, style);
+		kernel_modules.refresh()?;
+		Ok(kernel_modules)
+	}
+
+	/// Create an instance without the modules.
+	fn empty(args: ListArgs, style: Style) -> Self {
+		Self {
 			default_list: Vec::new(),
 			list: Vec::new(),
 			current_name: String::new(),
 			current_info: StyledText::default(),
+			info_cache: ModuleInfoCache::new(),
 			command: ModuleCommand::None,
+			command_generation: 0,
+			protected: ProtectedModules::default(),
+			confirmation: None,
+			dry_run: false,
+			read_only: false,
+			escalation: Escalation::default(),
+			audit_log: AuditLog::default(),
+			suspended: false,
+			dry_run_log: Vec::new(),
+			history: History::default(),
+			marked: Vec::new(),
+			batch: Vec::new(),
+			snapshot: None,
+			snapshot_changes: HashMap::new(),
+			module_events: Vec::new(),
+			pending_selection: None,
+			audit_error: None,
 			index: 0,
 			info_scroll_offset: 0,
+			dependency_tree: None,
+			options_editor: None,
+			graph_format: GraphFormat::default(),
+			view: ModuleView::Loaded,
+			builtin_modules: Vec::new(),
 			args,
 			style,
-		};
-		if let Err(e) = kernel_modules.refresh() {
-			eprintln!("{e}");
 		}
-		kernel_modules
 	}
 
-	/// Parse kernel modules from '/proc/modules'.
+	/// Refresh the module list depending on the current view.
 	pub fn refresh(&mut self) -> Result<(), Box<dyn Error>> {
-		let mut module_list: Vec<Vec<String>> = Vec::new();
-		// Set the command for reading kernel modules and execute it.
-		let mut module_read_cmd = String::from("cat /proc/modules");
-		match self.args.sort {
-			SortType::Size => module_read_cmd += " | sort -n -r -t ' ' -k2",
-			SortType::Name => module_read_cmd += " | sort -t ' ' -k1",
-			SortType::Dependent => module_read_cmd += " | sort -n -r -t ' ' -k3",
-			_ => {}
+		self.update_list()?;
+		self.info_cache.clear();
+		self.scroll_list(ScrollDirection::Top);
+		// Keep the audit error of the executed command visible.
+		if let Some((cmd, error)) = self.audit_error.take() {
+			self.show_command_error(
+				"Executed command without an audit record:",
+				&cmd,
+				&error,
+			);
+		}
+		Ok(())
+	}
+
+	/// Update the rows of the module list depending on the current view.
+	fn update_list(&mut self) -> Result<(), Box<dyn Error>> {
+		self.builtin_modules = modinfo::builtin_modules(&modinfo::modules_dir());
+		self.builtin_modules.sort();
+		let mut module_list = match self.view {
+			ModuleView::Loaded => self.loaded_modules()?,
+			ModuleView::Available => self.available_modules(),
+			ModuleView::Config => self.configured_modules(),
+		};
+		// Mark the modules that are configured at boot.
+		if self.view != ModuleView::Config {
+			let persistent_modules = autoload::persistent_modules();
+			for module in module_list.iter_mut() {
+				if module[0]
+					.split_whitespace()
+					.next()
+					.is_some_and(|name| persistent_modules.contains(name))
+				{
+					module[0] = format!("{} {PERSISTENT_MARKER}", module[0]);
+				}
+			}
+		}
+		if self.view == ModuleView::Loaded {
+			self.mark_snapshot_changes(&mut module_list);
+			self.mark_module_events(&mut module_list);
+		}
+		// Reverse the kernel modules if the argument is provided.
+		if self.args.reverse {
+			module_list.reverse();
+		}
+		self.default_list.clone_from(&module_list);
+		self.list = module_list;
+		Ok(())
+	}
+
+	/// Mark the modules that are recently loaded or unloaded.
+	///
+	/// Unloaded modules are kept in the list until their highlight expires.
+	fn mark_module_events(&self, module_list: &mut Vec<Vec<String>>) {
+		for (event, _) in &self.module_events {
+			let marker = match event.action {
+				ModuleAction::Load => LOADED_MARKER,
+				ModuleAction::Unload => UNLOADED_MARKER,
+			};
+			match module_list.iter_mut().find(|module| {
+				module[0].split_whitespace().next() == Some(&event.module)
+			}) {
+				Some(module) => module[0] = format!("{} {marker}", module[0]),
+				None if event.action == ModuleAction::Unload => {
+					module_list.push(vec![
+						format!(" {} {marker}", event.module),
+						String::from("-"),
+						String::from("-"),
+					]);
+				}
+				None => {}
+			}
+		}
+	}
+
+	/// Update the module list with a module that is loaded or unloaded.
+	///
+	/// Selected module and the current command are kept.
+	pub fn add_module_event(&mut self, event: ModuleEvent) {
+		self.info_cache.remove(&event.module);
+		let is_shown = self.current_name == event.module
+			&& self.command.is_none()
+			&& self.options_editor.is_none()
+			&& self.dependency_tree.is_none();
+		self.module_events.retain(|(v, _)| v.module != event.module);
+		self.module_events.push((event, Instant::now()));
+		self.update_events();
+		// Fetch the information of the shown module again.
+		if is_shown {
+			self.show_module_info();
+		}
+	}
+
+	/// Remove the highlights of the module events that are expired.
+	pub fn expire_module_events(&mut self) {
+		let count = self.module_events.len();
+		self.module_events
+			.retain(|(_, time)| time.elapsed() < EVENT_HIGHLIGHT_DURATION);
+		if self.module_events.len() != count {
+			self.update_events();
 		}
-		let modules_content = util::exec_cmd("sh", &["-c", &module_read_cmd])?;
+	}
+
+	/// Update the list of the loaded modules without changing the selection.
+	fn update_events(&mut self) {
+		if self.view != ModuleView::Loaded {
+			return;
+		}
+		// Rows are not drawn yet if the list is updated more than once.
+		if self.pending_selection.is_none() {
+			self.pending_selection = self.module_name_at(self.index);
+		}
+		let _ = self.update_list();
+	}
+
+	/// Select the current module again after the list is updated.
+	///
+	/// First module is selected if the shown module is not listed anymore.
+	/// Commands, titles and editors that are shown instead of the module
+	/// information are kept.
+	pub fn restore_selection(&mut self) {
+		let Some(name) = self.pending_selection.take() else {
+			return;
+		};
+		match self
+			.list
+			.iter()
+			.position(|module| module[0].split_whitespace().next() == Some(&name))
+		{
+			Some(index) => self.index = index,
+			None if self.current_name == name
+				&& self.command.is_none()
+				&& self.options_editor.is_none()
+				&& self.dependency_tree.is_none() =>
+			{
+				self.scroll_list(ScrollDirection::Top)
+			}
+			None => self.index = self.index.min(self.list.len().saturating_sub(1)),
+		}
+	}
 
-		// Parse content for module name, size and related information.
+	/// Mark the changes of the loaded modules since the snapshot.
+	///
+	/// Removed modules are appended to the list with their recorded sizes.
+	fn mark_snapshot_changes(&mut self, module_list: &mut Vec<Vec<String>>) {
+		self.snapshot_changes.clear();
+		let Some(snapshot) = &self.snapshot else {
+			return;
+		};
+		let Ok(live_snapshot) = Snapshot::live() else {
+			return;
+		};
+		let changes = snapshot.diff(&live_snapshot).changes;
+		for module in module_list.iter_mut() {
+			let name = module[0].split_whitespace().next().unwrap_or_default();
+			if let Some(change) = changes.iter().find(|v| v.module == name) {
+				module[0] = format!("{} {}", module[0], change.kind.marker());
+			}
+		}
+		for change in changes.iter().filter(|v| v.kind == ChangeKind::Removed) {
+			module_list.push(vec![
+				format!(" {} {REMOVED_MARKER}", change.module),
+				snapshot
+					.modules
+					.get(&change.module)
+					.map_or(String::from("-"), |v| {
+						ByteSize::b(v.size).to_string_as(true)
+					}),
+				String::from("-"),
+			]);
+		}
+		self.snapshot_changes = changes
+			.into_iter()
+			.map(|change| (change.module.to_string(), change))
+			.collect();
+	}
+
+	/// Set the snapshot that the loaded modules are compared with.
+	pub fn set_snapshot(
+		&mut self,
+		snapshot: Snapshot,
+	) -> Result<(), Box<dyn Error>> {
+		self.snapshot = Some(snapshot);
+		self.refresh()
+	}
+
+	/// Switch to the next view of the module table.
+	pub fn switch_view(&mut self) -> Result<(), Box<dyn Error>> {
+		self.view = self.view.next().unwrap_or(ModuleView::Loaded);
+		self.index = 0;
+		self.marked.clear();
+		self.refresh()
+	}
+
+	/// Parse kernel modules from '/proc/modules' along with the built-in modules.
+	fn loaded_modules(&self) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+		// Kernels without loadable module support only have built-in modules.
+		let modules_content = fs::read_to_string("/proc/modules").or_else(|e| {
+			if self.builtin_modules.is_empty() {
+				Err(e)
+			} else {
+				Ok(String::new())
+			}
+		})?;
+		// Parse content for module name, size, used count and related information.
+		let mut modules = Vec::new();
 		for line in modules_content.lines() {
 			let columns: Vec<&str> = line.split_whitespace().collect();
 			let mut module_name = format!(" {}", columns[0]);
@@ -122,23 +628,181 @@ impl KernelModules<'_> {
 			if used_modules.ends_with(',') {
 				used_modules.pop();
 			}
-			let module_size =
-				ByteSize::b(columns[1].parse().unwrap_or(0)).to_string_as(true);
-			module_list.push(vec![module_name, module_size, used_modules]);
+			modules.push((
+				module_name,
+				columns[1].parse::<u64>().ok(),
+				columns[2].parse::<u64>().unwrap_or(0),
+				used_modules,
+			));
 		}
-		// Reverse the kernel modules if the argument is provided.
-		if self.args.reverse {
-			module_list.reverse();
+		for module_name in &self.builtin_modules {
+			modules.push((
+				format!(" {module_name} {BUILTIN_MARKER}"),
+				None,
+				0,
+				String::from("-"),
+			));
 		}
-		self.default_list.clone_from(&module_list);
-		self.list = module_list;
-		self.scroll_list(ScrollDirection::Top);
-		Ok(())
+		match self.args.sort {
+			SortType::Size => modules.sort_by_key(|v| std::cmp::Reverse(v.1)),
+			SortType::Name => modules.sort_by(|a, b| a.0.cmp(&b.0)),
+			SortType::Dependent => modules.sort_by_key(|v| std::cmp::Reverse(v.2)),
+			_ => {}
+		}
+		Ok(modules
+			.into_iter()
+			.map(|(module_name, module_size, _, used_modules)| {
+				let module_size = module_size.map_or(String::from("-"), |v| {
+					ByteSize::b(v).to_string_as(true)
+				});
+				vec![module_name, module_size, used_modules]
+			})
+			.collect())
+	}
+
+	/// Check if the current module is built into the kernel.
+	pub fn is_builtin(&self) -> bool {
+		self.builtin_modules.contains(&self.current_name)
+	}
+
+	/// Parse the available modules of the running kernel from 'modules.dep'.
+	///
+	/// Modules are marked as loaded, unloaded, built-in and blacklisted.
+	fn available_modules(&self) -> Vec<Vec<String>> {
+		let modules_dir = modinfo::modules_dir();
+		let loaded_modules = fs::read_to_string("/proc/modules")
+			.unwrap_or_default()
+			.lines()
+			.filter_map(|line| line.split_whitespace().next())
+			.map(String::from)
+			.collect::<HashSet<String>>();
+		let blacklisted_modules = modprobe::blacklisted_modules(
+			&modprobe::config_files(modprobe::CONFIG_DIRS),
+		);
+		let mut module_list = modinfo::module_files(&modules_dir)
+			.into_iter()
+			.map(|(name, path)| {
+				let status = if loaded_modules.contains(&name) {
+					"loaded"
+				} else {
+					"unloaded"
+				};
+				(name, status, path)
+			})
+			.chain(
+				modinfo::builtin_modules(&modules_dir)
+					.into_iter()
+					.map(|name| (name, "built-in", String::from("(builtin)"))),
+			)
+			.map(|(name, status, path)| {
+				let status = if blacklisted_modules.contains(&name) {
+					format!("{status}, blacklisted")
+				} else {
+					status.to_string()
+				};
+				vec![format!(" {name}"), status, path]
+			})
+			.collect::<Vec<Vec<String>>>();
+		module_list.sort();
+		module_list
+	}
+
+	/// Parse the entries of the modprobe configuration files.
+	///
+	/// Entries are listed in the order of the files with their sources.
+	fn configured_modules(&self) -> Vec<Vec<String>> {
+		modprobe::config_entries(&modprobe::config_files(modprobe::CONFIG_DIRS))
+			.into_iter()
+			.map(|entry| {
+				vec![
+					format!(" {}", entry.module),
+					entry.source(),
+					format!(
+						"{}{} {}",
+						if entry.disabled { "# " } else { "" },
+						entry.directive,
+						entry.args
+					)
+					.trim_end()
+					.to_string(),
+				]
+			})
+			.collect()
+	}
+
+	/// Complete the given module name using the available modules.
+	///
+	/// Returns the longest common prefix of the matching module names.
+	pub fn complete_module_name(&self, prefix: &str) -> Option<String> {
+		let prefix = modinfo::module_name(prefix);
+		let mut candidates = modinfo::module_files(&modinfo::modules_dir())
+			.into_iter()
+			.map(|(name, _)| name)
+			.filter(|name| name.starts_with(&prefix));
+		let first = candidates.next()?;
+		let completion = candidates.fold(first, |completion, name| {
+			completion
+				.chars()
+				.zip(name.chars())
+				.take_while(|(a, b)| a == b)
+				.map(|(a, _)| a)
+				.collect()
+		});
+		(completion.len() > prefix.len()).then_some(completion)
 	}
 
 	/// Get the current command using current module name.
 	pub fn get_current_command(&self) -> Command {
-		self.command.get(&self.current_name)
+		let mut command = self.command.get(&self.current_name);
+		match (self.command, &self.options_editor) {
+			(ModuleCommand::SetOptions, Some(editor)) => {
+				command = command.with_writes(vec![editor.write()]);
+			}
+			(ModuleCommand::Undo, _) => {
+				if let Some(operation) = self.history.last_undoable() {
+					if let Some(cmd) = operation.undo_command() {
+						command.cmd = cmd;
+					}
+					command.writes = operation.undo_writes();
+				}
+			}
+			(command_type, _)
+				if !self.batch.is_empty() && !command_type.is_none() =>
+			{
+				let commands = self.batch_commands();
+				command.cmd = commands
+					.iter()
+					.map(|v| v.cmd.as_str())
+					.collect::<Vec<&str>>()
+					.join("\n");
+				command.writes =
+					commands.into_iter().flat_map(|v| v.writes).collect();
+			}
+			_ => {}
+		}
+		command
+	}
+
+	/// Return the commands of the batch in the order of execution.
+	///
+	/// Blacklist entries are added to the contents that are written by the
+	/// previous commands, as they are when the commands are executed.
+	fn batch_commands(&self) -> Vec<Command> {
+		let path = Path::new(modprobe::KMON_CONFIG);
+		let mut contents = fs::read_to_string(path).unwrap_or_default();
+		self.batch
+			.iter()
+			.map(|name| match self.command {
+				ModuleCommand::Blacklist => {
+					contents = modprobe::blacklist(&contents, name);
+					self.command.get(name).with_writes(vec![FileWrite::new(
+						path,
+						contents.to_string(),
+					)])
+				}
+				command => command.get(name),
+			})
+			.collect()
 	}
 
 	/// Set the current module command and show confirmation message.
@@ -147,84 +811,701 @@ impl KernelModules<'_> {
 		module_command: ModuleCommand,
 		command_name: String,
 	) {
-		if !command_name.contains(' ') && !self.current_name.starts_with('!') {
+		if self.read_only {
+			return;
+		}
+		match self.command_rejection(module_command, &command_name) {
+			None => {}
+			Some(_) if self.changes_builtin(module_command, &command_name) => {
+				if !self.marked.is_empty() {
+					self.current_name = self.marked.join(", ");
+				}
+				self.show_builtin_message(module_command);
+				return;
+			}
+			Some(_) => return,
+		}
+		if self.is_batch(module_command, &command_name) {
+			// Built-in modules are skipped in the batch.
+			let marked = self
+				.marked
+				.iter()
+				.filter(|name| !self.builtin_modules.contains(name))
+				.cloned()
+				.collect::<Vec<String>>();
+			// Holders are processed before the modules that they hold.
+			self.batch = ModuleGraph::loaded()
+				.unwrap_or_default()
+				.removal_order(&marked);
+			self.current_name = self.batch.join(", ");
+		} else {
+			self.batch.clear();
 			if !command_name.is_empty() {
 				self.current_name = command_name;
 			}
-			self.command = module_command;
-			self.current_info.set(
-				Text::from({
-					let mut spans = vec![
-						Line::from(Span::styled(
-							"Execute the following command? [y/N]:",
-							self.style.colored,
-						)),
-						Line::from(Span::styled(
-							self.get_current_command().cmd,
-							self.style.default,
-						)),
-						Line::default(),
-					];
-					spans.append(
-						&mut Text::styled(
-							self.get_current_command().desc,
-							self.style.colored,
+		}
+		self.command = module_command;
+		self.command_generation += 1;
+		self.confirmation = self.required_confirmation().map(|_| String::new());
+		self.show_command(self.command_preview());
+		self.info_scroll_offset = 0;
+	}
+
+	/// Check if the command is applied to the marked modules.
+	fn is_batch(&self, module_command: ModuleCommand, command_name: &str) -> bool {
+		module_command.is_destructive()
+			&& command_name.is_empty()
+			&& !self.marked.is_empty()
+	}
+
+	/// Check if the command would change only built-in modules.
+	fn changes_builtin(
+		&self,
+		module_command: ModuleCommand,
+		command_name: &str,
+	) -> bool {
+		if !module_command.is_destructive() || !command_name.is_empty() {
+			false
+		} else if self.marked.is_empty() {
+			self.is_builtin()
+		} else {
+			self.marked
+				.iter()
+				.all(|name| self.builtin_modules.contains(name))
+		}
+	}
+
+	/// Return the reason of not setting the module command, if any.
+	pub fn command_rejection(
+		&self,
+		module_command: ModuleCommand,
+		command_name: &str,
+	) -> Option<String> {
+		if self.read_only {
+			Some(String::from("commands are disabled in the read-only mode"))
+		} else if self.changes_builtin(module_command, command_name) {
+			Some(format!(
+				"built-in modules cannot be changed: {}",
+				if self.marked.is_empty() {
+					self.current_name.to_string()
+				} else {
+					self.marked.join(", ")
+				}
+			))
+		} else if self.is_batch(module_command, command_name) {
+			None
+		} else if command_name.contains(' ') {
+			Some(format!("module name contains a space: {command_name}"))
+		} else if self.current_name.starts_with('!') {
+			Some(String::from("no module is selected"))
+		} else {
+			None
+		}
+	}
+
+	/// Show the confirmation message of the current command.
+	fn show_command(&mut self, mut impact: Vec<Line<'static>>) {
+		let commands = if self.batch.is_empty() {
+			"command"
+		} else {
+			"commands"
+		};
+		let mut spans = match (&self.confirmation, self.required_confirmation()) {
+			(Some(confirmation), Some((names, reason))) => {
+				let mut spans = vec![Line::from(Span::styled(
+					if names.contains(' ') {
+						format!(
+							"{names} are protected ({reason}), type their names \
+							and press enter to execute the following {commands}:"
 						)
-						.lines,
-					);
-					spans
-				}),
-				self.get_current_command().cmd,
+					} else {
+						format!(
+							"{names} is protected ({reason}), type its name \
+							and press enter to execute the following {commands}:"
+						)
+					},
+					self.style.bold,
+				))];
+				spans.append(
+					&mut Text::styled(
+						self.get_current_command().cmd,
+						self.style.default,
+					)
+					.lines,
+				);
+				spans.push(Line::from(vec![
+					Span::styled("> ", self.style.colored),
+					Span::styled(confirmation.to_string(), self.style.default),
+				]));
+				spans
+			}
+			_ => {
+				let mut spans = vec![Line::from(Span::styled(
+					format!("Execute the following {commands}? [y/N]:"),
+					self.style.colored,
+				))];
+				spans.append(
+					&mut Text::styled(
+						self.get_current_command().cmd,
+						self.style.default,
+					)
+					.lines,
+				);
+				spans
+			}
+		};
+		spans.push(Line::default());
+		spans.append(&mut impact);
+		spans.append(
+			&mut Text::styled(self.get_current_command().desc, self.style.colored)
+				.lines,
+		);
+		self.current_info
+			.set(Text::from(spans), self.get_current_command().cmd);
+	}
+
+	/// Append a character to the typed confirmation of the current command.
+	pub fn push_confirmation(&mut self, c: char) {
+		self.edit_confirmation(|confirmation| confirmation.push(c));
+	}
+
+	/// Delete the last character of the typed confirmation.
+	pub fn pop_confirmation(&mut self) {
+		self.edit_confirmation(|confirmation| {
+			confirmation.pop();
+		});
+	}
+
+	/// Edit the typed confirmation and show it with the command.
+	fn edit_confirmation(&mut self, edit: impl FnOnce(&mut String)) {
+		if let Some(confirmation) = self.confirmation.as_mut() {
+			edit(confirmation);
+			self.show_command(self.command_preview());
+		}
+	}
+
+	/// Return the preview of the current command as lines.
+	///
+	/// Preview is the impact of unloading the module or the difference of
+	/// the configuration file that is changed by the command.
+	fn command_preview(&self) -> Vec<Line<'static>> {
+		match (self.command, &self.options_editor) {
+			_ if !self.batch.is_empty() => self.skipped_builtins(),
+			(ModuleCommand::SetOptions, Some(editor)) => {
+				let mut lines = vec![Line::from(Span::styled(
+					format!("Changes in {}:", modprobe::KMON_CONFIG),
+					self.style.colored,
+				))];
+				for line in editor.diff() {
+					lines.push(Line::from(Span::styled(
+						line.to_string(),
+						if line.starts_with(' ') {
+							self.style.default
+						} else {
+							self.style.bold
+						},
+					)));
+				}
+				lines.push(Line::default());
+				lines
+			}
+			_ => self.unload_impact(),
+		}
+	}
+
+	/// Return the built-in modules that are marked but skipped in the batch
+	/// as lines.
+	fn skipped_builtins(&self) -> Vec<Line<'static>> {
+		let names = self
+			.marked
+			.iter()
+			.filter(|name| self.builtin_modules.contains(name))
+			.map(String::as_str)
+			.collect::<Vec<&str>>();
+		if names.is_empty() {
+			return Vec::new();
+		}
+		vec![
+			Line::from(Span::styled(
+				"Built-in modules are skipped:",
+				self.style.colored,
+			)),
+			Line::from(Span::styled(names.join(", "), self.style.default)),
+			Line::default(),
+		]
+	}
+
+	/// Return the impact of unloading the current module as lines.
+	///
+	/// Lines are empty if the current command does not unload the module.
+	fn unload_impact(&self) -> Vec<Line<'static>> {
+		if !matches!(self.command, ModuleCommand::Unload | ModuleCommand::Reload) {
+			return Vec::new();
+		}
+		let Ok(graph) = ModuleGraph::loaded() else {
+			return Vec::new();
+		};
+		let impact = UnloadImpact::new(
+			&graph,
+			Path::new(sysfs::SYS_MODULE_DIR),
+			&self.current_name,
+		);
+		let mut lines = vec![Line::from(Span::styled(
+			"Impact:",
+			if impact.is_in_use() {
+				self.style.bold
+			} else {
+				self.style.colored
+			},
+		))];
+		for line in impact.to_string().lines() {
+			let (key, value) = line.split_once(": ").unwrap_or((line, ""));
+			lines.push(Line::from(vec![
+				Span::styled(format!("{key}: "), self.style.colored),
+				Span::styled(value.to_string(), self.style.default),
+			]));
+		}
+		lines.push(Line::default());
+		lines
+	}
+
+	/// Show the reason of not executing the command for a built-in module.
+	fn show_builtin_message(&mut self, module_command: ModuleCommand) {
+		self.command = ModuleCommand::None;
+		let message = format!(
+			"{0} is compiled into the kernel and it is not listed in /proc/modules, \
+			so it cannot be {1} with modprobe/rmmod. \
+			Its parameters might be changed at runtime via \
+			/sys/module/{0}/parameters or set on the kernel command line \
+			as {0}.<parameter>=<value>.",
+			self.current_name,
+			match module_command {
+				ModuleCommand::Unload => "unloaded",
+				ModuleCommand::Reload => "reloaded",
+				_ => "blacklisted",
+			}
+		);
+		self.current_info.set(
+			Text::from(vec![
+				Line::from(Span::styled(
+					"Built-in modules cannot be managed:",
+					self.style.colored,
+				)),
+				Line::from(Span::styled(
+					self.current_name.to_string(),
+					self.style.default,
+				)),
+				Line::default(),
+				Line::from(Span::styled(message.to_string(), self.style.colored)),
+			]),
+			message,
+		);
+		self.info_scroll_offset = 0;
+	}
+
+	/// Check if the current command is confirmed.
+	///
+	/// Commands of the protected modules require their names to be typed.
+	pub fn is_confirmed(&self) -> bool {
+		match self.required_confirmation() {
+			Some((names, _)) => self.confirmation.as_deref() == Some(names.as_str()),
+			None => true,
+		}
+	}
+
+	/// Return the names of the protected modules that have to be typed for
+	/// confirming the current command along with the reasons, if any.
+	pub fn required_confirmation(&self) -> Option<(String, String)> {
+		let modules = if self.batch.is_empty() {
+			std::slice::from_ref(&self.current_name)
+		} else {
+			self.batch.as_slice()
+		};
+		let (names, reasons): (Vec<&str>, Vec<&str>) = modules
+			.iter()
+			.filter_map(|name| {
+				self.protected
+					.requires_confirmation(self.command, name)
+					.map(|reason| (name.as_str(), reason))
+			})
+			.unzip();
+		(!names.is_empty()).then(|| (names.join(" "), reasons.join(", ")))
+	}
+
+	/// Return the actions of the current command.
+	///
+	/// Actions are the command itself, the files that it writes along with
+	/// their changes and the system calls that it performs.
+	pub fn command_actions(&self) -> Vec<String> {
+		self.actions_of(
+			&self.current_name,
+			&self.get_current_command(),
+			&mut HashMap::new(),
+		)
+	}
+
+	/// Return the actions of a command that is executed for the module.
+	///
+	/// Changes are compared with the given contents of the files that are
+	/// written by the previous commands, which are updated afterwards.
+	fn actions_of(
+		&self,
+		name: &str,
+		command: &Command,
+		files: &mut HashMap<PathBuf, String>,
+	) -> Vec<String> {
+		let mut args = self.escalation.command(&command.cmd, false);
+		args.pop();
+		let mut actions = vec![format!(
+			"exec: {} {}",
+			args.join(" "),
+			util::shell_quote(&command.cmd)
+		)];
+		for write in &command.writes {
+			actions.push(format!("write: {}", write.path.display()));
+			let previous = files.remove(&write.path).unwrap_or_else(|| {
+				fs::read_to_string(&write.path).unwrap_or_default()
+			});
+			actions.extend(
+				util::diff_lines(&previous, &write.contents)
+					.into_iter()
+					.map(|line| format!("  {line}")),
 			);
-			self.info_scroll_offset = 0;
+			files.insert(write.path.to_path_buf(), write.contents.to_string());
+		}
+		for syscall in self.command.syscalls(name) {
+			actions.push(format!("syscall: {syscall}"));
+		}
+		actions
+	}
+
+	/// Show the actions of the current command instead of executing it and
+	/// append them to the dry-run log.
+	fn show_dry_run(&mut self) {
+		let title = self.get_current_command().title;
+		let actions = if self.batch.is_empty() {
+			self.command_actions()
+		} else {
+			let mut files = HashMap::new();
+			let mut actions = Vec::new();
+			for (name, command) in self.batch.iter().zip(self.batch_commands()) {
+				actions.extend(self.actions_of(name, &command, &mut files));
+			}
+			self.batch.clear();
+			actions
+		};
+		self.dry_run_log.push(format!("# {title}"));
+		self.dry_run_log.extend(actions.iter().cloned());
+		let mut spans = vec![
+			Line::from(Span::styled(
+				"Dry run, the following actions are not performed:",
+				self.style.colored,
+			)),
+			Line::default(),
+		];
+		for action in &actions {
+			let (key, value) = action.split_once(": ").unwrap_or(("", action));
+			spans.push(Line::from(vec![
+				Span::styled(
+					if key.is_empty() {
+						String::new()
+					} else {
+						format!("{key}: ")
+					},
+					self.style.colored,
+				),
+				Span::styled(value.to_string(), self.style.default),
+			]));
+		}
+		self.current_info.set(Text::from(spans), actions.join("\n"));
+		self.current_name = format!(
+			"!Dry run: {title}{}",
+			self.style.unicode.get(Symbol::Magnifier)
+		);
+	}
+
+	/// Enable or disable the dry-run mode.
+	pub fn toggle_dry_run(&mut self) {
+		self.dry_run = !self.dry_run && !self.read_only;
+	}
+
+	/// Return the name of the mode that prevents the changes, if any.
+	pub fn mode(&self) -> Option<&'static str> {
+		if self.read_only {
+			Some("read-only")
+		} else if self.dry_run {
+			Some("dry-run")
+		} else {
+			None
+		}
+	}
+
+	/// Show the actions that are logged in the dry-run mode.
+	pub fn show_dry_run_log(&mut self) {
+		self.dependency_tree = None;
+		self.options_editor = None;
+		self.command = ModuleCommand::None;
+		self.confirmation = None;
+		self.info_scroll_offset = 0;
+		self.current_name =
+			format!("!Dry-run log{}", self.style.unicode.get(Symbol::Magnifier));
+		let log = if self.dry_run_log.is_empty() {
+			String::from("no commands are logged")
+		} else {
+			self.dry_run_log.join("\n")
+		};
+		self.current_info.set(
+			Text::from(
+				log.lines()
+					.map(|line| {
+						Line::from(Span::styled(
+							line.to_string(),
+							if line.starts_with('#') {
+								self.style.colored
+							} else {
+								self.style.default
+							},
+						))
+					})
+					.collect::<Vec<Line>>(),
+			),
+			log,
+		);
+	}
+
+	/// Show the confirmation of undoing the last module operation.
+	///
+	/// History is shown if there is no operation to undo.
+	pub fn undo(&mut self) {
+		if self.read_only {
+			return;
+		}
+		match self.history.last_undoable().map(|v| v.module.to_string()) {
+			Some(module) => {
+				self.dependency_tree = None;
+				self.options_editor = None;
+				self.current_name = module;
+				self.set_current_command(ModuleCommand::Undo, String::new());
+			}
+			None => self.show_history(),
+		}
+	}
+
+	/// Show the history of the executed operations.
+	pub fn show_history(&mut self) {
+		self.dependency_tree = None;
+		self.options_editor = None;
+		self.command = ModuleCommand::None;
+		self.confirmation = None;
+		self.info_scroll_offset = 0;
+		self.current_name =
+			format!("!History{}", self.style.unicode.get(Symbol::Magnifier));
+		let (text, raw_text) = self.history.render(&self.style);
+		self.current_info.set(text, raw_text);
+	}
+
+	/// Run the command with the privilege escalation helper.
+	///
+	/// Terminal UI is suspended if the helper prompts for a password.
+	fn run_command(&mut self, cmd: &str) -> io::Result<Output> {
+		let non_interactive =
+			!self.escalation.is_needed() || self.escalation.is_non_interactive();
+		let args = self.escalation.command(cmd, non_interactive);
+		let args = args.iter().map(String::as_str).collect::<Vec<&str>>();
+		if non_interactive {
+			process::Command::new(args[0]).args(&args[1..]).output()
+		} else {
+			self.suspended = true;
+			util::exec_cmd_interactive(
+				&format!(
+					"kmon: {} requires root privileges for running the command:\n\
+					{cmd}\n",
+					self.escalation.program()
+				),
+				args[0],
+				&args[1..],
+			)
+			// Output of the helper is shown on the terminal.
+			.map(|status| Output {
+				status,
+				stdout: Vec::new(),
+				stderr: Vec::new(),
+			})
+		}
+	}
+
+	/// Run the current command and write it to the audit log.
+	///
+	/// Command is not run if the audit log is not writable. Returns the
+	/// error of the audit log if the record of the command is not written.
+	fn audit_current_command(&mut self) -> Result<Option<String>, String> {
+		self.audit_log
+			.check()
+			.map_err(|e| format!("audit log is not writable: {e}"))?;
+		let cmd = self.get_current_command().cmd;
+		let parameters = match (self.command, &self.options_editor) {
+			(ModuleCommand::SetOptions, Some(editor)) => editor.options(),
+			(ModuleCommand::OptionsAtBoot, _) => {
+				ModuleCommand::runtime_options(&self.current_name)
+			}
+			(ModuleCommand::Undo, _) => self
+				.history
+				.last_undoable()
+				.map(|v| v.parameters.to_string())
+				.unwrap_or_default(),
+			_ => String::new(),
+		};
+		let output = self.run_command(&cmd);
+		let record = AuditRecord::new(
+			self.command,
+			&self.current_name,
+			&parameters,
+			&cmd,
+			&output,
+		);
+		let audit_error = self
+			.audit_log
+			.write(&record)
+			.err()
+			.map(|e| format!("failed to write the audit log: {e}"));
+		match output {
+			Ok(output) if output.status.success() => Ok(audit_error),
+			Ok(output) if record.stderr.is_empty() => Err(output.status.to_string()),
+			Ok(_) => Err(record.stderr.to_string()),
+			Err(e) => Err(e.to_string()),
 		}
 	}
 
 	/// Execute the current module command.
+	///
+	/// Actions of the command are only shown in the dry-run mode.
 	pub fn execute_command(&mut self) -> bool {
 		let mut command_executed = false;
-		if !self.command.is_none() {
-			match util::exec_cmd("sh", &["-c", &self.get_current_command().cmd]) {
-				Ok(_) => command_executed = true,
-				Err(e) => {
-					self.current_info.set(
-						Text::from({
-							let mut spans = vec![
-								Line::from(Span::styled(
-									"Failed to execute command:",
-									self.style.colored,
-								)),
-								Line::from(Span::styled(
-									format!("'{}'", self.get_current_command().cmd),
-									self.style.default,
-								)),
-								Line::default(),
-							];
-							spans.append(
-								&mut Text::styled(e.to_string(), self.style.default)
-									.lines,
-							);
-							spans
-						}),
-						format!(
-							"Execution Error\n'{}'\n{}",
-							self.get_current_command().cmd,
-							e
-						),
-					);
-					self.current_name =
-						format!("!Error{}", self.style.unicode.get(Symbol::NoEntry));
+		if !self.command.is_none() && self.is_confirmed() {
+			if self.dry_run {
+				self.show_dry_run();
+			} else if !self.batch.is_empty() {
+				self.execute_batch();
+			} else {
+				let operation = Operation::new(
+					self.command,
+					&self.current_name,
+					&self.get_current_command().writes,
+				);
+				let result = self.audit_current_command();
+				if result.is_ok() {
+					self.history.push(operation);
+				}
+				// Command is executed even if its record is not written.
+				match result {
+					Ok(audit_error) => {
+						self.audit_error =
+							audit_error.map(|e| (self.get_current_command().cmd, e));
+						command_executed = true;
+					}
+					Err(e) => self.show_command_error(
+						"Failed to execute command:",
+						&self.get_current_command().cmd,
+						&e,
+					),
 				}
 			}
 			self.command = ModuleCommand::None;
+			self.confirmation = None;
+			self.options_editor = None;
+			self.batch.clear();
 		}
 		command_executed
 	}
 
+	/// Show the error of the current command in the module information.
+	fn show_command_error(&mut self, heading: &str, cmd: &str, error: &str) {
+		let mut lines = vec![
+			Line::from(Span::styled(heading.to_string(), self.style.colored)),
+			Line::from(Span::styled(format!("'{cmd}'"), self.style.default)),
+			Line::default(),
+		];
+		lines.append(&mut Text::styled(error.to_string(), self.style.default).lines);
+		self.current_info.set(
+			Text::from(lines),
+			format!("Execution Error\n'{cmd}'\n{error}"),
+		);
+		self.current_name =
+			format!("!Error{}", self.style.unicode.get(Symbol::NoEntry));
+	}
+
+	/// Execute the current command for each module of the batch and show
+	/// the results.
+	///
+	/// Module list is refreshed here for keeping the results visible.
+	fn execute_batch(&mut self) {
+		let title = self.get_current_command().title;
+		let mut results = Vec::new();
+		for name in mem::take(&mut self.batch) {
+			self.current_name = name.to_string();
+			let operation = Operation::new(
+				self.command,
+				&name,
+				&self.get_current_command().writes,
+			);
+			let result = self.audit_current_command();
+			if result.is_ok() {
+				self.history.push(operation);
+			}
+			results.push((
+				name,
+				result.map(|audit_error| audit_error.unwrap_or_default()),
+			));
+		}
+		self.marked.clear();
+		if let Err(e) = self.refresh() {
+			results.push((String::from("refresh"), Err(e.to_string())));
+		}
+		let succeeded = results.iter().filter(|(_, v)| v.is_ok()).count();
+		let mut spans = vec![
+			Line::from(Span::styled(
+				format!("{succeeded} of {} succeeded:", results.len()),
+				self.style.colored,
+			)),
+			Line::default(),
+		];
+		let mut raw_lines = Vec::new();
+		for (name, result) in &results {
+			let status = match result {
+				Ok(audit_error) if audit_error.is_empty() => String::from("ok"),
+				Ok(audit_error) => format!("ok ({audit_error})"),
+				Err(e) => format!("failed ({})", e.trim().replace('\n', " ")),
+			};
+			spans.push(Line::from(vec![
+				Span::styled(format!("{name}: "), self.style.colored),
+				Span::styled(
+					status.to_string(),
+					if result.is_ok() {
+						self.style.default
+					} else {
+						self.style.bold
+					},
+				),
+			]));
+			raw_lines.push(format!("{name}: {status}"));
+		}
+		self.current_info
+			.set(Text::from(spans), raw_lines.join("\n"));
+		self.current_name =
+			format!("!{title}{}", self.style.unicode.get(Symbol::FuelPump));
+	}
+
 	/// Cancel the execution of the current command.
 	pub fn cancel_execution(&mut self) -> bool {
+		self.confirmation = None;
 		if !self.command.is_none() {
 			self.command = ModuleCommand::None;
+			self.batch.clear();
+			// Return to the options editor if the options are being saved.
+			if self.options_editor.is_some() {
+				self.show_options_editor_title();
+				return true;
+			}
 			if self.index != 0 {
 				self.index -= 1;
 				self.scroll_list(ScrollDirection::Down);
@@ -246,32 +1527,248 @@ impl KernelModules<'_> {
 			.get(1)
 			.unwrap_or(&"")
 			.split(',')
-			.collect::<Vec<&str>>()
+			.map(String::from)
+			.collect::<Vec<String>>()
 			.get(mod_index)
 		{
-			if let Some(v) = self
-				.list
-				.iter()
-				.position(|module| module[0] == format!(" {used_module}"))
-			{
-				match v {
-					0 => {
-						self.index = v + 1;
-						self.scroll_list(ScrollDirection::Up);
-					}
-					v if v > 0 => {
-						self.index = v - 1;
-						self.scroll_list(ScrollDirection::Down);
-					}
-					_ => {}
+			self.select_module(used_module);
+		}
+	}
+
+	/// Scroll to the position of the module with given name.
+	///
+	/// Returns false if the module is not in the list.
+	pub fn select_module(&mut self, name: &str) -> bool {
+		match self
+			.list
+			.iter()
+			.position(|module| module[0].split_whitespace().next() == Some(name))
+		{
+			Some(0) => {
+				self.index = 1;
+				self.scroll_list(ScrollDirection::Up);
+				true
+			}
+			Some(v) => {
+				self.index = v - 1;
+				self.scroll_list(ScrollDirection::Down);
+				true
+			}
+			None => false,
+		}
+	}
+
+	/// Return the name of the module at the given index of the list.
+	fn module_name_at(&self, index: usize) -> Option<String> {
+		self.list
+			.get(index)
+			.and_then(|module| module[0].split_whitespace().next())
+			.map(String::from)
+	}
+
+	/// Mark or unmark the selected module for the batch commands.
+	pub fn toggle_mark(&mut self) {
+		if let Some(name) = self.module_name_at(self.index) {
+			match self.marked.iter().position(|v| *v == name) {
+				Some(position) => {
+					self.marked.remove(position);
 				}
+				None => self.marked.push(name),
 			}
 		}
 	}
 
+	/// Mark the modules between the last marked module and the selected one.
+	pub fn mark_range(&mut self) {
+		let start = self
+			.marked
+			.last()
+			.and_then(|name| {
+				(0..self.list.len())
+					.find(|i| self.module_name_at(*i).as_ref() == Some(name))
+			})
+			.unwrap_or(self.index);
+		for index in start.min(self.index)..=start.max(self.index) {
+			if let Some(name) = self.module_name_at(index) {
+				if !self.marked.contains(&name) {
+					self.marked.push(name);
+				}
+			}
+		}
+	}
+
+	/// Check if the module is marked for the batch commands.
+	pub fn is_marked(&self, name: &str) -> bool {
+		self.marked.iter().any(|v| v == name)
+	}
+
+	/// Return the names of the marked modules or the current module name.
+	pub fn selected_names(&self) -> String {
+		if self.marked.is_empty() {
+			self.current_name.to_string()
+		} else {
+			self.marked.join(" ")
+		}
+	}
+
+	/// Show or hide the dependency tree of the current module.
+	pub fn show_dependency_tree(&mut self) {
+		if let Some(tree) = self.dependency_tree.take() {
+			self.current_name = tree.root().to_string();
+			self.info_scroll_offset = 0;
+			self.show_module_info();
+		} else if let Some(module) = self.list.get(self.index) {
+			let name = module[0].split_whitespace().next().unwrap_or("?");
+			self.dependency_tree = Some(DependencyTree::new(name));
+			self.options_editor = None;
+			self.command = ModuleCommand::None;
+			self.current_name = format!(
+				"!Dependencies of {name}{}",
+				self.style.unicode.get(Symbol::HistoricSite)
+			);
+			self.info_scroll_offset = 0;
+			self.show_dependency_nodes();
+		}
+	}
+
+	/// Show the dependency graph of the current module.
+	///
+	/// Format of the graph is changed if it is already shown.
+	pub fn show_dependency_graph(&mut self) {
+		let Some(module) = self.list.get(self.index) else {
+			return;
+		};
+		let name = module[0]
+			.split_whitespace()
+			.next()
+			.unwrap_or("?")
+			.to_string();
+		if self.current_name.starts_with("!Dependency graph") {
+			self.graph_format = self.graph_format.next();
+		}
+		self.dependency_tree = None;
+		self.options_editor = None;
+		self.command = ModuleCommand::None;
+		self.info_scroll_offset = 0;
+		self.current_name = format!(
+			"!Dependency graph of {name} ({}){}",
+			self.graph_format,
+			self.style.unicode.get(Symbol::HistoricSite)
+		);
+		let graph = match ModuleGraph::loaded() {
+			Ok(graph) if graph.nodes.contains_key(&name) => {
+				graph.subgraph(&name).export(self.graph_format)
+			}
+			Ok(_) => format!("{name} is not loaded"),
+			Err(e) => e.to_string(),
+		};
+		self.current_info
+			.set(Text::styled(graph.to_string(), self.style.default), graph);
+	}
+
+	/// Add style to the nodes of the dependency tree.
+	fn show_dependency_nodes(&mut self) {
+		if let Some(tree) = &self.dependency_tree {
+			let (text, raw_text) = tree.render(&self.style);
+			self.current_info.set(text, raw_text);
+		}
+	}
+
+	/// Select the next/previous node of the dependency tree.
+	pub fn scroll_dependency_tree(&mut self, direction: ScrollDirection) {
+		if let Some(tree) = self.dependency_tree.as_mut() {
+			match direction {
+				ScrollDirection::Up => tree.previous(),
+				ScrollDirection::Down => tree.next(),
+				_ => {}
+			}
+			self.show_dependency_nodes();
+		}
+	}
+
+	/// Expand or collapse the selected node of the dependency tree.
+	pub fn toggle_dependency_node(&mut self) {
+		if let Some(tree) = self.dependency_tree.as_mut() {
+			tree.toggle();
+			self.show_dependency_nodes();
+		}
+	}
+
+	/// Select the module of the selected node in the module table.
+	///
+	/// Returns true if the module is selected.
+	pub fn select_dependency_node(&mut self) -> bool {
+		match self
+			.dependency_tree
+			.as_ref()
+			.and_then(|tree| tree.selected_module())
+			.map(String::from)
+		{
+			Some(name) => self.select_module(&name),
+			None => false,
+		}
+	}
+
+	/// Show or hide the options editor of the current module.
+	pub fn show_options_editor(&mut self) {
+		if self.read_only {
+			return;
+		}
+		if let Some(editor) = self.options_editor.take() {
+			self.current_name = editor.module;
+			self.info_scroll_offset = 0;
+			self.show_module_info();
+		} else if let Some(module) = self.list.get(self.index) {
+			let name = module[0].split_whitespace().next().unwrap_or("?");
+			self.options_editor = Some(OptionsEditor::new(name));
+			self.dependency_tree = None;
+			self.command = ModuleCommand::None;
+			self.info_scroll_offset = 0;
+			self.show_options_editor_title();
+		}
+	}
+
+	/// Set the title of the options editor and show its parameters.
+	fn show_options_editor_title(&mut self) {
+		if let Some(editor) = &self.options_editor {
+			self.current_name = format!(
+				"!Options of {}{}",
+				editor.module,
+				self.style.unicode.get(Symbol::Gear)
+			);
+			self.show_options_parameters();
+		}
+	}
+
+	/// Add style to the parameters of the options editor.
+	fn show_options_parameters(&mut self) {
+		if let Some(editor) = &self.options_editor {
+			let (text, raw_text) = editor.render(&self.style);
+			self.current_info.set(text, raw_text);
+		}
+	}
+
+	/// Edit the options editor and show its parameters.
+	pub fn edit_options(&mut self, edit: impl FnOnce(&mut OptionsEditor)) {
+		if let Some(editor) = self.options_editor.as_mut() {
+			edit(editor);
+			self.show_options_parameters();
+		}
+	}
+
+	/// Show the confirmation of saving the edited options.
+	pub fn save_options(&mut self) {
+		if let Some(editor) = &self.options_editor {
+			self.current_name = editor.module.to_string();
+			self.set_current_command(ModuleCommand::SetOptions, String::new());
+		}
+	}
+
 	/// Scroll module list up/down and select module.
 	pub fn scroll_list(&mut self, direction: ScrollDirection) {
 		self.info_scroll_offset = 0;
+		self.dependency_tree = None;
+		self.options_editor = None;
 		if self.list.is_empty() {
 			self.index = 0;
 		} else {
@@ -290,26 +1787,66 @@ impl KernelModules<'_> {
 				.unwrap_or("?")
 				.trim()
 				.to_string();
-			// Execute 'modinfo' and add style to its output.
-			self.current_info.stylize_data(
-				Box::leak(
-					util::exec_cmd("modinfo", &[&self.current_name])
-						.unwrap_or_else(|e| {
-							format!("module information not available: {e}")
-						})
-						.replace("signature: ", "signature: \n")
-						.into_boxed_str(),
-				),
-				":",
-				self.style.clone(),
-			);
+			// Show the module information or its placeholder.
+			self.show_module_info();
 			// Clear the current command.
 			if !self.command.is_none() {
 				self.command = ModuleCommand::None;
+				self.confirmation = None;
+				self.batch.clear();
 			}
 		}
 	}
 
+	/// Add style to the cached information of the current module.
+	///
+	/// Shows a placeholder if the information is not fetched yet.
+	fn show_module_info(&mut self) {
+		match self.info_cache.get(&self.current_name) {
+			Some(module_info) => {
+				let mut module_info = module_info.to_string();
+				// Append the changes of the module since the snapshot.
+				if let Some(change) = self.snapshot_changes.get(&self.current_name) {
+					let kind = format!("{:?}", change.kind).to_lowercase();
+					for detail in change
+						.details
+						.iter()
+						.chain(change.details.is_empty().then_some(&kind))
+					{
+						let _ = write!(module_info, "\n{:<16}{detail}", "snapshot:");
+					}
+				}
+				self.current_info.stylize_data(
+					&module_info,
+					":",
+					self.style.clone(),
+				);
+			}
+			None => {
+				self.current_info.set(
+					Text::styled(LOADING_PLACEHOLDER, self.style.colored),
+					LOADING_PLACEHOLDER.to_string(),
+				);
+			}
+		}
+	}
+
+	/// Receive the fetched module information from the worker.
+	///
+	/// Returns true if the information of the current module is updated.
+	pub fn receive_info(&mut self) -> bool {
+		let received = self.info_cache.receive();
+		if received.contains(&self.current_name)
+			&& self.command.is_none()
+			&& self.current_info.raw_text == LOADING_PLACEHOLDER
+		{
+			self.show_module_info();
+			true
+		} else {
+			false
+		}
+	}
+
 	/// Select the next module.
 	pub fn next_module(&mut self) {
 		self.index += 1;
@@ -354,6 +1891,50 @@ impl KernelModules<'_> {
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use std::time::Duration;
+	#[test]
+	fn test_module_info_cache() {
+		let mut info_cache = ModuleInfoCache::new();
+		assert_eq!(None, info_cache.get("test"));
+		let mut received = Vec::new();
+		while received.is_empty() {
+			thread::sleep(Duration::from_millis(10));
+			received = info_cache.receive();
+		}
+		assert_eq!(vec![String::from("test")], received);
+		assert!(info_cache.get("test").is_some());
+		assert_eq!(None, info_cache.get("other"));
+		// Requests of the other modules are kept.
+		info_cache.remove("test");
+		assert!(!info_cache.cache.contains_key("test"));
+		received.clear();
+		while received.is_empty() {
+			thread::sleep(Duration::from_millis(10));
+			received = info_cache.receive();
+		}
+		assert_eq!(vec![String::from("other")], received);
+		info_cache.clear();
+		assert_eq!(None, info_cache.get("test"));
+	}
+	/// Return the name of a listed module that is not compiled into the
+	/// kernel.
+	fn removable_module(kernel_modules: &KernelModules) -> String {
+		kernel_modules
+			.list
+			.iter()
+			.filter_map(|module| module[0].split_whitespace().next())
+			.find(|name| !kernel_modules.builtin_modules.contains(&name.to_string()))
+			.unwrap_or_default()
+			.to_string()
+	}
+	/// Return the kernel modules along with a removable module.
+	fn kernel_modules() -> (KernelModules, String) {
+		let args = ArgMatches::default();
+		let kernel_modules =
+			KernelModules::new(ListArgs::new(&args), Style::new(&args));
+		let name = removable_module(&kernel_modules);
+		(kernel_modules, name)
+	}
 	#[test]
 	fn test_kernel_modules() {
 		let args = ArgMatches::default();
@@ -379,12 +1960,18 @@ mod tests {
 		assert_ne!(0, kernel_modules.default_list.len());
 		assert_ne!(0, kernel_modules.current_name.len());
 		assert_ne!(0, kernel_modules.current_info.lines());
+		let audit_log = util::temp_path("lkm-audit.log");
+		kernel_modules.audit_log.path = Some(audit_log.clone());
 		kernel_modules
 			.set_current_command(ModuleCommand::Load, String::from("test"));
 		assert_eq!("test", kernel_modules.current_name);
 		assert!(!kernel_modules.execute_command());
+		let record = fs::read_to_string(&audit_log).unwrap_or_default();
+		let _ = fs::remove_file(audit_log);
+		assert!(record.contains("\"command\":\"load\""));
+		assert!(record.contains("\"module\":\"test\""));
 		kernel_modules.set_current_command(ModuleCommand::Load, String::new());
-		kernel_modules.scroll_list(ScrollDirection::Top);
+		assert!(kernel_modules.select_module(&removable_module(&kernel_modules)));
 		for command in [
 			ModuleCommand::Unload,
 			ModuleCommand::Blacklist,
@@ -394,4 +1981,235 @@ mod tests {
 			assert_eq!(!command.is_none(), kernel_modules.cancel_execution());
 		}
 	}
+	#[test]
+	fn test_search_mode() {
+		let mut list_args = ListArgs::new(&ArgMatches::default());
+		for search_mode in
+			[SearchMode::Regex, SearchMode::Fuzzy, SearchMode::Substring]
+		{
+			list_args.next_search_mode();
+			assert_eq!(search_mode, list_args.search_mode());
+		}
+	}
+	#[test]
+	fn test_module_view() {
+		let (mut kernel_modules, _) = kernel_modules();
+		assert_eq!(None, kernel_modules.complete_module_name("~"));
+		assert!(kernel_modules.switch_view().is_ok());
+		assert_eq!(ModuleView::Available, kernel_modules.view);
+		assert!(kernel_modules.switch_view().is_ok());
+		assert_eq!(ModuleView::Config, kernel_modules.view);
+		assert_eq!("Configured", kernel_modules.view.to_string());
+		assert!(kernel_modules.switch_view().is_ok());
+		assert_eq!(ModuleView::Loaded, kernel_modules.view);
+	}
+	#[test]
+	fn test_audit_error() {
+		let (mut kernel_modules, _) = kernel_modules();
+		kernel_modules.audit_error =
+			Some((String::from("modprobe test"), String::from("denied")));
+		kernel_modules.refresh();
+		assert!(kernel_modules.current_name.starts_with("!Error"));
+		assert!(kernel_modules.current_info.raw_text.ends_with("\ndenied"));
+		assert_eq!(None, kernel_modules.audit_error);
+	}
+	#[test]
+	fn test_dependency_tree() {
+		let (mut kernel_modules, name) = kernel_modules();
+		assert!(kernel_modules.select_module(&name));
+		kernel_modules.show_dependency_tree();
+		assert!(kernel_modules.current_name.contains(&name));
+		kernel_modules.scroll_dependency_tree(ScrollDirection::Down);
+		kernel_modules.toggle_dependency_node();
+		kernel_modules.show_dependency_tree();
+		assert_eq!(name, kernel_modules.current_name);
+	}
+	#[test]
+	fn test_protected_modules() {
+		let (mut kernel_modules, name) = kernel_modules();
+		assert!(kernel_modules.select_module(&name));
+		kernel_modules.protected.add(&name, "test");
+		kernel_modules.set_current_command(ModuleCommand::Unload, String::new());
+		assert!(!kernel_modules.is_confirmed());
+		name.chars()
+			.for_each(|c| kernel_modules.push_confirmation(c));
+		kernel_modules.push_confirmation('~');
+		kernel_modules.pop_confirmation();
+		assert!(kernel_modules.is_confirmed());
+		assert!(kernel_modules.cancel_execution());
+		assert_eq!(None, kernel_modules.confirmation);
+	}
+	#[test]
+	fn test_history() {
+		let (mut kernel_modules, name) = kernel_modules();
+		kernel_modules.history.push(Operation {
+			command: ModuleCommand::Unload,
+			module: name.to_string(),
+			..Operation::default()
+		});
+		kernel_modules.undo();
+		assert_eq!(ModuleCommand::Undo, kernel_modules.command);
+		assert!(kernel_modules
+			.get_current_command()
+			.cmd
+			.starts_with(&format!("modprobe {name} ||")));
+		assert!(kernel_modules.cancel_execution());
+		kernel_modules.show_history();
+		assert!(kernel_modules
+			.current_info
+			.raw_text
+			.contains(&format!("unload {name} (undoable)")));
+	}
+	#[test]
+	fn test_module_events() {
+		let (mut kernel_modules, name) = kernel_modules();
+		assert!(kernel_modules.select_module(&name));
+		kernel_modules.set_current_command(ModuleCommand::Unload, String::new());
+		for (action, module) in
+			[(ModuleAction::Unload, "~"), (ModuleAction::Load, &name)]
+		{
+			kernel_modules.add_module_event(ModuleEvent {
+				action,
+				module: module.to_string(),
+			});
+		}
+		assert!(kernel_modules
+			.default_list
+			.iter()
+			.any(|module| module[0] == format!(" ~ {UNLOADED_MARKER}")));
+		kernel_modules.list = kernel_modules.default_list.clone();
+		kernel_modules.restore_selection();
+		assert_eq!(
+			Some(name.clone()),
+			kernel_modules.module_name_at(kernel_modules.index)
+		);
+		assert!(
+			kernel_modules.list[kernel_modules.index][0].ends_with(LOADED_MARKER)
+		);
+		assert!(kernel_modules.cancel_execution());
+		kernel_modules.set_current_command(ModuleCommand::Load, String::from("~"));
+		kernel_modules.pending_selection = Some(String::from("~"));
+		kernel_modules.restore_selection();
+		assert_eq!(ModuleCommand::Load, kernel_modules.command);
+		assert_eq!("~", kernel_modules.current_name);
+		assert!(kernel_modules.cancel_execution());
+		assert!(kernel_modules.select_module(&name));
+		kernel_modules.add_module_event(ModuleEvent {
+			action: ModuleAction::Load,
+			module: name.to_string(),
+		});
+		assert_eq!(LOADING_PLACEHOLDER, kernel_modules.current_info.raw_text);
+	}
+	#[test]
+	fn test_read_only() {
+		let (mut kernel_modules, name) = kernel_modules();
+		assert!(kernel_modules.select_module(&name));
+		kernel_modules.read_only = true;
+		assert_eq!(Some("read-only"), kernel_modules.mode());
+		kernel_modules.set_current_command(ModuleCommand::Unload, String::new());
+		assert!(kernel_modules.command.is_none());
+	}
+	#[test]
+	fn test_dry_run() {
+		let (mut kernel_modules, name) = kernel_modules();
+		assert!(kernel_modules.select_module(&name));
+		kernel_modules.toggle_dry_run();
+		assert_eq!(Some("dry-run"), kernel_modules.mode());
+		kernel_modules.set_current_command(ModuleCommand::Blacklist, String::new());
+		assert!(!kernel_modules.execute_command());
+		assert!(kernel_modules
+			.current_name
+			.starts_with("!Dry run: Blacklist"));
+		assert!(kernel_modules.dry_run_log[0].starts_with("# Blacklist: "));
+		assert!(kernel_modules.current_info.raw_text.contains(&format!(
+			"write: {}\n  +blacklist {name}\n",
+			modprobe::KMON_CONFIG
+		)));
+		kernel_modules.show_dry_run_log();
+		assert!(kernel_modules
+			.current_info
+			.raw_text
+			.contains("exec: sh -c "));
+	}
+	#[test]
+	fn test_marked_modules() {
+		let (mut kernel_modules, _) = kernel_modules();
+		kernel_modules.toggle_dry_run();
+		kernel_modules.scroll_list(ScrollDirection::Top);
+		kernel_modules.toggle_mark();
+		kernel_modules.scroll_list(ScrollDirection::Down);
+		kernel_modules.mark_range();
+		assert_eq!(2, kernel_modules.marked.len());
+		assert!(kernel_modules.selected_names().contains(' '));
+		kernel_modules.set_current_command(ModuleCommand::Unload, String::new());
+		// Built-in modules are skipped in the batch.
+		let count = kernel_modules.batch.len();
+		assert_eq!(
+			count,
+			kernel_modules.get_current_command().cmd.lines().count()
+		);
+		assert!(!kernel_modules.execute_command());
+		assert_eq!(
+			count,
+			kernel_modules
+				.current_info
+				.raw_text
+				.matches("exec: ")
+				.count()
+		);
+		kernel_modules.toggle_mark();
+		assert_eq!(1, kernel_modules.marked.len());
+		kernel_modules.builtin_modules.push(String::from("~c"));
+		kernel_modules.marked = vec![String::from("~c")];
+		kernel_modules.set_current_command(ModuleCommand::Blacklist, String::new());
+		assert!(kernel_modules.command.is_none());
+		assert!(kernel_modules
+			.current_info
+			.raw_text
+			.contains("~c is compiled into the kernel"));
+		kernel_modules.marked =
+			vec![String::from("~a"), String::from("~b"), String::from("~c")];
+		kernel_modules.set_current_command(ModuleCommand::Blacklist, String::new());
+		assert_eq!("~a, ~b", kernel_modules.current_name);
+		// Entries of the first module are kept in the second write.
+		assert_eq!(
+			2,
+			kernel_modules
+				.get_current_command()
+				.writes
+				.iter()
+				.filter(|v| v.contents.contains("blacklist ~a\n"))
+				.count()
+		);
+		assert!(!kernel_modules.execute_command());
+		assert_eq!(
+			1,
+			kernel_modules
+				.current_info
+				.raw_text
+				.matches("+blacklist ~a")
+				.count()
+		);
+	}
+	#[test]
+	fn test_batch() {
+		let (mut kernel_modules, _) = kernel_modules();
+		let audit_log = util::temp_path("lkm-batch-audit.log");
+		kernel_modules.audit_log.path = Some(audit_log.clone());
+		kernel_modules.marked = vec![String::from("~a"), String::from("~b")];
+		kernel_modules.protected.add("~b", "test");
+		kernel_modules.set_current_command(ModuleCommand::Unload, String::new());
+		assert_eq!("~a, ~b", kernel_modules.current_name);
+		assert!(!kernel_modules.is_confirmed());
+		"~b".chars()
+			.for_each(|c| kernel_modules.push_confirmation(c));
+		assert!(!kernel_modules.execute_command());
+		let _ = fs::remove_file(audit_log);
+		assert!(kernel_modules.marked.is_empty());
+		assert!(kernel_modules
+			.current_info
+			.raw_text
+			.starts_with("~a: failed"));
+		assert!(kernel_modules.current_name.starts_with("!Remove: ~a, ~b"));
+	}
 }
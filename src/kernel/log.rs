@@ -1,6 +1,92 @@
 use crate::app::ScrollDirection;
+use crate::kernel::modinfo;
 use crate::util;
-use std::fmt::Write as _;
+use clap::ArgMatches;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Write as _};
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+
+/// Arguments of 'dmesg' for reading the kernel logs
+const DMESG_ARGS: &[&str] = &["--kernel", "--human", "--ctime", "--color=never"];
+
+/// Log levels in the order of their priorities
+pub const LOG_LEVELS: &[&str] = &[
+	"emerg", "alert", "crit", "err", "warn", "notice", "info", "debug",
+];
+
+/// Record of the kernel logs
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LogRecord {
+	pub level: String,
+	pub time: String,
+	pub message: String,
+}
+
+impl LogRecord {
+	/// Parse a line of the decoded 'dmesg' output.
+	///
+	/// Continuation lines of a message inherit the level and time of the
+	/// previous record.
+	pub fn parse(line: &str, previous: Option<&Self>) -> Self {
+		let parts = line.split_once(':').and_then(|(_, line)| {
+			let (level, line) = line.split_once(':')?;
+			let (time, message) =
+				line.trim_start().strip_prefix('[')?.split_once(']')?;
+			Some((level.trim(), time.trim(), message.trim_start()))
+		});
+		match (parts, previous) {
+			(Some((level, time, message)), _) if LOG_LEVELS.contains(&level) => {
+				Self {
+					level: level.to_string(),
+					time: time.to_string(),
+					message: message.to_string(),
+				}
+			}
+			(_, Some(previous)) => Self {
+				message: line.trim().to_string(),
+				..previous.clone()
+			},
+			_ => Self {
+				message: line.trim().to_string(),
+				..Self::default()
+			},
+		}
+	}
+
+	/// Check if the record is at least as important as the given level.
+	pub fn has_level(&self, level: &str) -> bool {
+		let priority = |level: &str| LOG_LEVELS.iter().position(|v| *v == level);
+		match (priority(&self.level), priority(level)) {
+			(Some(priority), Some(level)) => priority <= level,
+			_ => true,
+		}
+	}
+
+	/// Check if the message mentions the given module.
+	pub fn mentions(&self, module: &str) -> bool {
+		let module = modinfo::module_name(module);
+		self.message
+			.split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+			.any(|word| modinfo::module_name(word) == module)
+	}
+
+	/// Return the record as a JSON line.
+	pub fn to_json(&self) -> String {
+		serde_json::json!({
+			"level": self.level,
+			"time": self.time,
+			"message": self.message,
+		})
+		.to_string()
+	}
+}
+
+impl Display for LogRecord {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "[{}] {}: {}", self.time, self.level, self.message)
+	}
+}
 
 /// Kernel activity logs
 #[derive(Clone, Debug, Default)]
@@ -15,11 +101,8 @@ pub struct KernelLogs {
 impl KernelLogs {
 	/// Update the output variable value if 'dmesg' logs changed.
 	pub fn update(&mut self) -> bool {
-		self.output = util::exec_cmd(
-			"dmesg",
-			&["--kernel", "--human", "--ctime", "--color=never"],
-		)
-		.unwrap_or_else(|e| format!("failed to retrieve dmesg output: {e}"));
+		self.output = util::exec_cmd("dmesg", DMESG_ARGS)
+			.unwrap_or_else(|e| format!("failed to retrieve dmesg output: {e}"));
 		let logs_updated =
 			self.output.lines().next_back().unwrap_or_default() != self.last_line;
 		self.last_line = self
@@ -91,6 +174,63 @@ impl KernelLogs {
 	}
 }
 
+/// Pass the records of the kernel logs to the given function until it
+/// returns false.
+///
+/// New records are passed as they arrive if `follow` is true.
+pub fn watch(
+	follow: bool,
+	mut handler: impl FnMut(&LogRecord) -> bool,
+) -> Result<(), Box<dyn Error>> {
+	let mut args = DMESG_ARGS.to_vec();
+	args.push("--decode");
+	if follow {
+		args.push("--follow");
+	}
+	let mut child = Command::new("dmesg")
+		.args(args)
+		.stdout(Stdio::piped())
+		.spawn()?;
+	let stdout = child.stdout.take().ok_or("failed to read dmesg output")?;
+	let mut previous: Option<LogRecord> = None;
+	for line in BufReader::new(stdout).lines() {
+		let record = LogRecord::parse(&line?, previous.as_ref());
+		if !handler(&record) {
+			let _ = child.kill();
+			return Ok(());
+		}
+		previous = Some(record);
+	}
+	if !child.wait()?.success() {
+		return Err("failed to retrieve dmesg output".into());
+	}
+	Ok(())
+}
+
+/// Print the kernel logs for the `logs` subcommand.
+///
+/// Records are read with the same 'dmesg' arguments as the kernel activities
+/// and the new records are printed as they arrive if `--follow` is given.
+pub fn run(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+	let level = matches.get_one::<String>("level");
+	let module = matches.get_one::<String>("module");
+	let mut output = io::stdout().lock();
+	watch(matches.get_flag("follow"), |record| {
+		if level.is_none_or(|v| record.has_level(v))
+			&& module.is_none_or(|v| record.mentions(v))
+		{
+			let line = if matches.get_flag("json") {
+				record.to_json()
+			} else {
+				record.to_string()
+			};
+			// Stop reading when the output is closed (e.g. by 'head').
+			return writeln!(output, "{line}").is_ok();
+		}
+		true
+	})
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -105,4 +245,28 @@ mod tests {
 		assert_ne!(0, kernel_logs.output.lines().count());
 		assert_ne!(0, kernel_logs.select(10, 2).len());
 	}
+	#[test]
+	fn test_log_record() {
+		let record = LogRecord::parse(
+			"kern  :warn  : [Sat Jan  1 00:00:00 2000] snd_hda_intel 0000:00:1f.3: \
+			azx_get_response timeout",
+			None,
+		);
+		assert_eq!("warn", record.level);
+		assert_eq!("Sat Jan  1 00:00:00 2000", record.time);
+		assert!(record.has_level("warn") && !record.has_level("err"));
+		assert!(record.mentions("snd-hda-intel") && !record.mentions("snd"));
+		let continuation = LogRecord::parse("  next line: value", Some(&record));
+		assert_eq!("next line: value", continuation.message);
+		assert_eq!("warn", continuation.level);
+		assert_eq!(
+			r#"{"level":"warn","message":"next line: value","time":"Sat Jan  1 00:00:00 2000"}"#,
+			continuation.to_json()
+		);
+		assert_eq!(
+			"[Sat Jan  1 00:00:00 2000] warn: snd_hda_intel 0000:00:1f.3: \
+			azx_get_response timeout",
+			record.to_string()
+		);
+	}
 }
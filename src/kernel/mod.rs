@@ -1,27 +1,62 @@
+pub mod audit;
+pub mod autoload;
 pub mod cmd;
+pub mod deps;
+pub mod details;
+pub mod exporter;
+pub mod graph;
+pub mod history;
+pub mod impact;
 pub mod info;
+pub mod list;
 pub mod lkm;
 pub mod log;
+pub mod modinfo;
+pub mod modprobe;
+pub mod params;
+pub mod profile;
+pub mod snapshot;
+pub mod sysfs;
+pub mod uevent;
 use crate::style::Style;
+use audit::AuditLog;
 use clap::ArgMatches;
+use cmd::{Escalation, ProtectedModules};
 use info::KernelInfo;
 use lkm::{KernelModules, ListArgs};
 use log::KernelLogs;
+use snapshot::Snapshot;
+use std::path::Path;
 
 /// Kernel struct for logs, information and modules
 pub struct Kernel {
 	pub logs: KernelLogs,
 	pub info: KernelInfo,
-	pub modules: KernelModules<'static>,
+	pub modules: KernelModules,
 }
 
 impl Kernel {
 	/// Create a new kernel instance.
 	pub fn new(args: &ArgMatches) -> Self {
+		let mut modules = KernelModules::new(ListArgs::new(args), Style::new(args));
+		modules.protected = ProtectedModules::new(args);
+		modules.escalation = Escalation::new(args);
+		modules.audit_log = AuditLog::new(args);
+		modules.dry_run =
+			args.try_get_one::<bool>("dry-run").ok().flatten() == Some(&true);
+		modules.read_only =
+			args.try_get_one::<bool>("read-only").ok().flatten() == Some(&true);
+		if let Some(path) = args.try_get_one::<String>("compare").ok().flatten() {
+			if let Err(e) = Snapshot::read(Path::new(path))
+				.and_then(|snapshot| modules.set_snapshot(snapshot))
+			{
+				eprintln!("{e}");
+			}
+		}
 		Self {
 			logs: KernelLogs::default(),
 			info: KernelInfo::default(),
-			modules: KernelModules::new(ListArgs::new(args), Style::new(args)),
+			modules,
 		}
 	}
 
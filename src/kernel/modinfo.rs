@@ -0,0 +1,576 @@
+use crate::util;
+use flate2::read::GzDecoder;
+use ruzstd::decoding::StreamingDecoder;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Write as _};
+use std::fs;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// Magic string at the end of the signed module files
+const SIGNATURE_MAGIC: &[u8] = b"~Module signature appended~\n";
+
+/// Size of the signature information struct before the magic string
+const SIGNATURE_INFO_SIZE: usize = 12;
+
+/// Identifier type of the PKCS#7 signatures
+const PKEY_ID_PKCS7: u8 = 2;
+
+/// Names of the signature identifier types
+const SIGNATURE_ID_TYPES: &[&str] = &["PGP", "X509", "PKCS#7"];
+
+/// Names of the hash algorithms used for the module signatures
+const HASH_ALGORITHMS: &[&str] = &[
+	"md4", "md5", "sha1", "rmd160", "sha256", "sha384", "sha512", "sha224",
+	"rmd128", "rmd256", "rmd320", "wp256", "wp384", "wp512", "tgr128", "tgr160",
+	"tgr192", "sm3",
+];
+
+/// DER encoded object identifiers of the hash algorithms
+const HASH_ALGORITHM_OIDS: &[(&[u8], &str)] = &[
+	(&[0x2b, 0x0e, 0x03, 0x02, 0x1a], "sha1"),
+	(
+		&[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01],
+		"sha256",
+	),
+	(
+		&[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02],
+		"sha384",
+	),
+	(
+		&[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03],
+		"sha512",
+	),
+	(
+		&[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x04],
+		"sha224",
+	),
+	(&[0x2a, 0x81, 0x1c, 0xcf, 0x55, 0x01, 0x83, 0x11], "sm3"),
+];
+
+/// DER encoded object identifier of the common name attribute
+const COMMON_NAME_OID: &[u8] = &[0x55, 0x04, 0x03];
+
+/// Return the directory of the modules that belong to the running kernel.
+pub fn modules_dir() -> PathBuf {
+	let release = fs::read_to_string("/proc/sys/kernel/osrelease")
+		.map(|v| v.trim().to_string())
+		.or_else(|_| util::exec_cmd("uname", &["-r"]))
+		.unwrap_or_default();
+	Path::new("/lib/modules").join(release)
+}
+
+/// Return the module name from the path of a module file.
+///
+/// Dashes are replaced with underscores as the kernel does.
+pub fn module_name(path: &str) -> String {
+	let file_name = path.rsplit('/').next().unwrap_or(path);
+	file_name
+		.split_once(".ko")
+		.map_or(file_name, |(name, _)| name)
+		.replace('-', "_")
+}
+
+/// Find the file of a module using the 'modules.dep' file.
+pub fn find_module_file(modules_dir: &Path, name: &str) -> Option<PathBuf> {
+	let name = module_name(name);
+	fs::read_to_string(modules_dir.join("modules.dep"))
+		.ok()?
+		.lines()
+		.filter_map(|line| line.split_once(':'))
+		.find(|(path, _)| module_name(path) == name)
+		.map(|(path, _)| modules_dir.join(path))
+}
+
+/// Return the files of the modules that a module depends on using the
+/// 'modules.dep' file.
+///
+/// Files are listed in the order that modprobe loads them.
+pub fn dependency_files(modules_dir: &Path, name: &str) -> Vec<PathBuf> {
+	let name = module_name(name);
+	fs::read_to_string(modules_dir.join("modules.dep"))
+		.unwrap_or_default()
+		.lines()
+		.filter_map(|line| line.split_once(':'))
+		.find(|(path, _)| module_name(path) == name)
+		.map(|(_, depends)| {
+			depends
+				.split_whitespace()
+				.rev()
+				.map(|path| modules_dir.join(path))
+				.collect()
+		})
+		.unwrap_or_default()
+}
+
+/// Return the names and relative paths of the modules in 'modules.dep'.
+pub fn module_files(modules_dir: &Path) -> Vec<(String, String)> {
+	fs::read_to_string(modules_dir.join("modules.dep"))
+		.unwrap_or_default()
+		.lines()
+		.filter_map(|line| line.split_once(':'))
+		.map(|(path, _)| (module_name(path), path.to_string()))
+		.collect()
+}
+
+/// Return the names of the built-in modules in 'modules.builtin'.
+pub fn builtin_modules(modules_dir: &Path) -> Vec<String> {
+	fs::read_to_string(modules_dir.join("modules.builtin"))
+		.unwrap_or_default()
+		.lines()
+		.filter(|line| !line.trim().is_empty())
+		.map(module_name)
+		.collect()
+}
+
+/// Read a module file and decompress it depending on its extension.
+pub fn read_module_file(path: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
+	let file = fs::File::open(path)?;
+	let mut data = Vec::new();
+	match path.extension().and_then(|v| v.to_str()) {
+		Some("xz") => {
+			lzma_rs::xz_decompress(&mut BufReader::new(file), &mut data)?;
+		}
+		Some("zst") => {
+			StreamingDecoder::new(file)?.read_to_end(&mut data)?;
+		}
+		Some("gz") => {
+			GzDecoder::new(file).read_to_end(&mut data)?;
+		}
+		_ => {
+			BufReader::new(file).read_to_end(&mut data)?;
+		}
+	}
+	Ok(data)
+}
+
+/// Return the contents of an ELF section by its name.
+pub fn elf_section<'a>(data: &'a [u8], section_name: &str) -> Option<&'a [u8]> {
+	if data.get(..4)? != b"\x7fELF" {
+		return None;
+	}
+	let is_64 = *data.get(4)? == 2;
+	let is_le = *data.get(5)? == 1;
+	// Offsets of malformed files may overflow.
+	let slice = |offset: usize, size: usize| -> Option<&'a [u8]> {
+		data.get(offset..offset.checked_add(size)?)
+	};
+	let read = |offset: usize, size: usize| -> Option<usize> {
+		let bytes = slice(offset, size)?;
+		let mut value = 0usize;
+		for i in 0..size {
+			let byte = if is_le { bytes[size - 1 - i] } else { bytes[i] };
+			value = (value << 8) | byte as usize;
+		}
+		Some(value)
+	};
+	let (shoff, shentsize, shnum, shstrndx) = if is_64 {
+		(
+			read(0x28, 8)?,
+			read(0x3a, 2)?,
+			read(0x3c, 2)?,
+			read(0x3e, 2)?,
+		)
+	} else {
+		(
+			read(0x20, 4)?,
+			read(0x2e, 2)?,
+			read(0x30, 2)?,
+			read(0x32, 2)?,
+		)
+	};
+	// Return the name offset, file offset and size of a section header.
+	let section = |index: usize| -> Option<(usize, usize, usize)> {
+		let header = index.checked_mul(shentsize)?.checked_add(shoff)?;
+		if is_64 {
+			Some((
+				read(header, 4)?,
+				read(header.checked_add(0x18)?, 8)?,
+				read(header.checked_add(0x20)?, 8)?,
+			))
+		} else {
+			Some((
+				read(header, 4)?,
+				read(header.checked_add(0x10)?, 4)?,
+				read(header.checked_add(0x14)?, 4)?,
+			))
+		}
+	};
+	let (_, strtab_offset, strtab_size) = section(shstrndx)?;
+	let strtab = slice(strtab_offset, strtab_size)?;
+	(0..shnum).find_map(|index| {
+		let (name_offset, offset, size) = section(index)?;
+		let name = strtab.get(name_offset..)?.split(|v| *v == 0).next()?;
+		if name == section_name.as_bytes() {
+			slice(offset, size)
+		} else {
+			None
+		}
+	})
+}
+
+/// Parse the NUL separated 'key=value' entries of a modinfo section.
+fn parse_fields(data: &[u8]) -> Vec<(String, String)> {
+	data.split(|v| *v == 0)
+		.filter_map(|entry| {
+			let entry = String::from_utf8_lossy(entry);
+			entry
+				.split_once('=')
+				.map(|(key, value)| (key.to_string(), value.to_string()))
+		})
+		.collect()
+}
+
+/// Format the bytes as colon separated hexadecimal values.
+fn to_hex(data: &[u8]) -> String {
+	data.iter()
+		.map(|v| format!("{v:02X}"))
+		.collect::<Vec<String>>()
+		.join(":")
+}
+
+/// Read a DER element and return its tag, contents and the remaining data.
+fn read_der(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+	let tag = *data.first()?;
+	let first = *data.get(1)? as usize;
+	let (length, header) = if first & 0x80 == 0 {
+		(first, 2)
+	} else {
+		let count = first & 0x7f;
+		let length = data
+			.get(2..2 + count)?
+			.iter()
+			.fold(0usize, |acc, v| (acc << 8) | *v as usize);
+		(length, 2 + count)
+	};
+	let contents = data.get(header..header + length)?;
+	Some((tag, contents, &data[header + length..]))
+}
+
+/// Extract the signer, key and hash algorithm from a PKCS#7 signature.
+fn parse_pkcs7(data: &[u8]) -> Option<(String, String, String, Vec<u8>)> {
+	// ContentInfo -> [0] SignedData
+	let (_, content_info, _) = read_der(data)?;
+	let (_, _, rest) = read_der(content_info)?;
+	let (_, signed_data, _) = read_der(rest)?;
+	let (_, signed_data, _) = read_der(signed_data)?;
+	// Skip version, digestAlgorithms, contentInfo and the optional fields.
+	let mut rest = signed_data;
+	let mut signer_infos = None;
+	while let Some((tag, contents, next)) = read_der(rest) {
+		if tag == 0x31 && next.is_empty() {
+			signer_infos = Some(contents);
+		}
+		rest = next;
+	}
+	let (_, signer_info, _) = read_der(signer_infos?)?;
+	let (_, _, rest) = read_der(signer_info)?;
+	// IssuerAndSerialNumber
+	let (_, issuer_and_serial, rest) = read_der(rest)?;
+	let (_, mut issuer, rest_serial) = read_der(issuer_and_serial)?;
+	let (_, serial, _) = read_der(rest_serial)?;
+	let mut signer = String::new();
+	while let Some((_, rdn, next)) = read_der(issuer) {
+		let (_, attribute, _) = read_der(rdn)?;
+		let (_, oid, value) = read_der(attribute)?;
+		if oid == COMMON_NAME_OID {
+			signer = String::from_utf8_lossy(read_der(value)?.1).to_string();
+		}
+		issuer = next;
+	}
+	// DigestAlgorithm
+	let (_, digest_algorithm, mut rest) = read_der(rest)?;
+	let (_, oid, _) = read_der(digest_algorithm)?;
+	let hash_algorithm = HASH_ALGORITHM_OIDS
+		.iter()
+		.find(|(v, _)| *v == oid)
+		.map_or("unknown", |(_, name)| name)
+		.to_string();
+	// Skip to the encryptedDigest.
+	let mut signature = Vec::new();
+	while let Some((tag, contents, next)) = read_der(rest) {
+		if tag == 0x04 {
+			signature = contents.to_vec();
+		}
+		rest = next;
+	}
+	Some((signer, to_hex(serial), hash_algorithm, signature))
+}
+
+/// Parse the signature that is appended to the module file.
+fn parse_signature(data: &[u8]) -> Vec<(String, String)> {
+	let mut fields = Vec::new();
+	let Some(data) = data.strip_suffix(SIGNATURE_MAGIC) else {
+		return fields;
+	};
+	let Some(info) = data
+		.len()
+		.checked_sub(SIGNATURE_INFO_SIZE)
+		.map(|offset| &data[offset..])
+	else {
+		return fields;
+	};
+	let (hash, id_type, signer_len, key_id_len) =
+		(info[1], info[2], info[3] as usize, info[4] as usize);
+	let sig_len =
+		u32::from_be_bytes([info[8], info[9], info[10], info[11]]) as usize;
+	let data = &data[..data.len() - SIGNATURE_INFO_SIZE];
+	let Some(start) = data.len().checked_sub(sig_len + key_id_len + signer_len)
+	else {
+		return fields;
+	};
+	let signer = &data[start..start + signer_len];
+	let key_id = &data[start + signer_len..start + signer_len + key_id_len];
+	let signature = &data[data.len() - sig_len..];
+	let id_type_name = SIGNATURE_ID_TYPES
+		.get(id_type as usize)
+		.unwrap_or(&"unknown")
+		.to_string();
+	let (signer, key_id, hash_algorithm, signature) = if id_type == PKEY_ID_PKCS7 {
+		parse_pkcs7(signature).unwrap_or_default()
+	} else {
+		(
+			String::from_utf8_lossy(signer).to_string(),
+			to_hex(key_id),
+			HASH_ALGORITHMS
+				.get(hash as usize)
+				.unwrap_or(&"unknown")
+				.to_string(),
+			signature.to_vec(),
+		)
+	};
+	fields.push((String::from("sig_id"), id_type_name));
+	if !signer.is_empty() {
+		fields.push((String::from("signer"), signer));
+	}
+	if !key_id.is_empty() {
+		fields.push((String::from("sig_key"), key_id));
+	}
+	fields.push((String::from("sig_hashalgo"), hash_algorithm));
+	if !signature.is_empty() {
+		// Wrap the signature with 20 bytes on each line as modinfo does.
+		let signature = signature
+			.chunks(20)
+			.map(to_hex)
+			.collect::<Vec<String>>()
+			.join(":\n\t\t");
+		fields.push((String::from("signature"), signature));
+	}
+	fields
+}
+
+/// Kernel module information read from the module files
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ModuleInfo {
+	pub filename: String,
+	pub fields: Vec<(String, String)>,
+}
+
+impl ModuleInfo {
+	/// Read the information of a loadable or built-in module.
+	pub fn read(name: &str) -> Result<Self, Box<dyn Error>> {
+		let modules_dir = modules_dir();
+		if let Some(path) = find_module_file(&modules_dir, name) {
+			Self::from_file(&path)
+		} else {
+			Self::from_builtin(&modules_dir, name)
+		}
+	}
+
+	/// Read the information of a module from its file.
+	pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+		let data = read_module_file(path)?;
+		let section = elf_section(&data, ".modinfo")
+			.ok_or("failed to find the .modinfo section")?;
+		let mut fields = parse_fields(section);
+		fields.append(&mut parse_signature(&data));
+		Ok(Self {
+			filename: path.to_string_lossy().to_string(),
+			fields,
+		})
+	}
+
+	/// Read the information of a built-in module from 'modules.builtin.modinfo'.
+	pub fn from_builtin(
+		modules_dir: &Path,
+		name: &str,
+	) -> Result<Self, Box<dyn Error>> {
+		let name = module_name(name);
+		let prefix = format!("{name}.");
+		let data = fs::read(modules_dir.join("modules.builtin.modinfo"))?;
+		let mut fields = vec![(String::from("name"), name.to_string())];
+		fields.extend(parse_fields(&data).into_iter().filter_map(|(key, value)| {
+			key.strip_prefix(&prefix)
+				.map(|key| (key.to_string(), value))
+		}));
+		if fields.len() == 1 {
+			return Err(format!("Module {name} not found.").into());
+		}
+		Ok(Self {
+			filename: String::from("(builtin)"),
+			fields,
+		})
+	}
+
+	/// Return the first value of the given field.
+	pub fn get(&self, key: &str) -> Option<&str> {
+		self.fields
+			.iter()
+			.find(|(k, _)| k == key)
+			.map(|(_, v)| v.as_str())
+	}
+
+	/// Return the names, descriptions and types of the module parameters.
+	pub fn parameters(&self) -> Vec<(&str, Option<&str>, Option<&str>)> {
+		let mut params: Vec<(&str, Option<&str>, Option<&str>)> = Vec::new();
+		for (key, value) in &self.fields {
+			// Merge the parameter descriptions with their types.
+			if let ("parm" | "parmtype", Some((name, desc))) =
+				(key.as_str(), value.split_once(':'))
+			{
+				let index =
+					params.iter().position(|v| v.0 == name).unwrap_or_else(|| {
+						params.push((name, None, None));
+						params.len() - 1
+					});
+				if key == "parm" {
+					params[index].1 = Some(desc);
+				} else {
+					params[index].2 = Some(desc);
+				}
+			}
+		}
+		params
+	}
+}
+
+/// Implementation of Display for formatting the information as modinfo does
+impl Display for ModuleInfo {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		let mut output = String::new();
+		let _ = writeln!(output, "{:<16}{}", "filename:", self.filename);
+		for (key, value) in &self.fields {
+			if !matches!(key.as_str(), "parm" | "parmtype") || !value.contains(':') {
+				let _ = writeln!(output, "{:<16}{value}", format!("{key}:"));
+			}
+		}
+		for (name, desc, param_type) in self.parameters() {
+			let _ =
+				write!(output, "{:<16}{name}:{}", "parm:", desc.unwrap_or_default());
+			let _ = match param_type {
+				Some(param_type) => writeln!(output, " ({param_type})"),
+				None => writeln!(output),
+			};
+		}
+		write!(f, "{}", output.trim_end())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use flate2::write::GzEncoder;
+	use flate2::Compression;
+	use std::io::Write;
+
+	/// Build a minimal 64-bit little-endian ELF file with a '.modinfo' section.
+	fn build_elf(modinfo: &[u8]) -> Vec<u8> {
+		let strtab = b"\0.modinfo\0.shstrtab\0";
+		let mut data = vec![0u8; 0x40];
+		data[..6].copy_from_slice(b"\x7fELF\x02\x01");
+		let modinfo_offset = data.len();
+		data.extend_from_slice(modinfo);
+		let strtab_offset = data.len();
+		data.extend_from_slice(strtab);
+		let shoff = data.len();
+		data[0x28..0x30].copy_from_slice(&(shoff as u64).to_le_bytes());
+		data[0x3a..0x3c].copy_from_slice(&64u16.to_le_bytes());
+		data[0x3c..0x3e].copy_from_slice(&3u16.to_le_bytes());
+		data[0x3e..0x40].copy_from_slice(&2u16.to_le_bytes());
+		for (name, offset, size) in [
+			(0u32, 0usize, 0usize),
+			(1, modinfo_offset, modinfo.len()),
+			(10, strtab_offset, strtab.len()),
+		] {
+			let mut header = vec![0u8; 64];
+			header[..4].copy_from_slice(&name.to_le_bytes());
+			header[0x18..0x20].copy_from_slice(&(offset as u64).to_le_bytes());
+			header[0x20..0x28].copy_from_slice(&(size as u64).to_le_bytes());
+			data.extend_from_slice(&header);
+		}
+		data
+	}
+
+	#[test]
+	fn test_module_info() -> Result<(), Box<dyn Error>> {
+		assert_eq!("nvidia_drm", module_name("kernel/nvidia-drm.ko.zst"));
+		assert_eq!("snd_hda_intel", module_name("snd-hda-intel"));
+		let mut data = build_elf(
+			b"license=GPL\0parm=debug:Enable debugging\0parmtype=debug:int\0\
+			parmtype=quiet:bool\0depends=snd,soundcore\0",
+		);
+		assert_eq!(None, elf_section(&data, ".text"));
+		// Offsets of the section headers overflow.
+		for shoff in [u64::MAX, u64::MAX - 0x10] {
+			let mut malformed = data.clone();
+			malformed[0x28..0x30].copy_from_slice(&shoff.to_le_bytes());
+			assert_eq!(None, elf_section(&malformed, ".modinfo"));
+		}
+		// Append a signature with 'sha256' and 'X509' types.
+		let signature = [0xab; 21];
+		data.extend_from_slice(b"signerkey");
+		data.extend_from_slice(&signature);
+		data.extend_from_slice(&[0, 4, 1, 6, 3, 0, 0, 0]);
+		data.extend_from_slice(&(signature.len() as u32).to_be_bytes());
+		data.extend_from_slice(SIGNATURE_MAGIC);
+		let dir = crate::util::temp_path("modinfo");
+		fs::create_dir_all(&dir)?;
+		let path = dir.join("test-module.ko.gz");
+		let mut encoder =
+			GzEncoder::new(fs::File::create(&path)?, Compression::fast());
+		encoder.write_all(&data)?;
+		encoder.finish()?;
+		fs::write(dir.join("modules.dep"), "test-module.ko.gz: snd.ko\n")?;
+		fs::write(
+			dir.join("modules.builtin.modinfo"),
+			b"ext4.license=GPL\0ext4.description=Fourth Extended Filesystem\0",
+		)?;
+		fs::write(dir.join("modules.builtin"), "kernel/fs/ext4/ext4.ko\n")?;
+		assert_eq!(Some(path.clone()), find_module_file(&dir, "test_module"));
+		assert_eq!(
+			vec![dir.join("snd.ko")],
+			dependency_files(&dir, "test-module")
+		);
+		assert_eq!(
+			vec![(
+				String::from("test_module"),
+				String::from("test-module.ko.gz")
+			)],
+			module_files(&dir)
+		);
+		assert_eq!(vec![String::from("ext4")], builtin_modules(&dir));
+		let module_info = ModuleInfo::from_file(&path)?;
+		assert_eq!(Some("snd,soundcore"), module_info.get("depends"));
+		assert_eq!(Some("signer"), module_info.get("signer"));
+		assert_eq!(Some("6B:65:79"), module_info.get("sig_key"));
+		assert_eq!(Some("sha256"), module_info.get("sig_hashalgo"));
+		let output = module_info.to_string();
+		assert!(output.contains("parm:           debug:Enable debugging (int)\n"));
+		assert!(output.ends_with("parm:           quiet: (bool)"));
+		assert!(output.contains("signature:      AB:"));
+		assert_eq!(
+			vec![
+				("debug", Some("Enable debugging"), Some("int")),
+				("quiet", None, Some("bool"))
+			],
+			module_info.parameters()
+		);
+		assert!(output.contains(":\n\t\tAB"));
+		let module_info = ModuleInfo::from_builtin(&dir, "ext4")?;
+		assert_eq!("(builtin)", module_info.filename);
+		assert_eq!(Some("GPL"), module_info.get("license"));
+		assert!(ModuleInfo::from_builtin(&dir, "ext").is_err());
+		fs::remove_dir_all(dir)?;
+		Ok(())
+	}
+}
@@ -0,0 +1,435 @@
+use crate::kernel::modinfo;
+use crate::util;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directories of the modprobe configuration files in the order of precedence
+pub const CONFIG_DIRS: &[&str] = &[
+	"/etc/modprobe.d",
+	"/run/modprobe.d",
+	"/usr/local/lib/modprobe.d",
+	"/usr/lib/modprobe.d",
+	"/lib/modprobe.d",
+];
+
+/// Configuration file that is managed by kmon
+pub const KMON_CONFIG: &str = "/etc/modprobe.d/kmon.conf";
+
+/// Prefix of the configuration lines that are disabled by kmon
+pub const DISABLED_PREFIX: &str = "# kmon: ";
+
+/// Directive in a modprobe configuration file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigEntry {
+	pub path: PathBuf,
+	pub line: usize,
+	pub line_count: usize,
+	pub directive: String,
+	pub module: String,
+	pub args: String,
+	pub disabled: bool,
+}
+
+impl ConfigEntry {
+	/// Return the source of the entry as the file name and line number.
+	pub fn source(&self) -> String {
+		format!(
+			"{}:{}",
+			self.path
+				.file_name()
+				.map(|v| v.to_string_lossy().to_string())
+				.unwrap_or_default(),
+			self.line
+		)
+	}
+
+	/// Check if the entry prevents the module from loading.
+	pub fn is_blacklist(&self) -> bool {
+		self.directive == "blacklist"
+			|| (self.directive == "install" && self.args == "/bin/false")
+	}
+}
+
+/// Return the configuration files in the given directories.
+///
+/// Files are sorted by their names and a file overrides the files with the
+/// same name in the directories that come after it.
+pub fn config_files(config_dirs: &[&str]) -> Vec<PathBuf> {
+	let mut files: Vec<PathBuf> = Vec::new();
+	for dir in config_dirs {
+		if let Ok(entries) = fs::read_dir(dir) {
+			for path in entries.flatten().map(|entry| entry.path()) {
+				if path.extension().and_then(|v| v.to_str()) == Some("conf")
+					&& !files.iter().any(|v| v.file_name() == path.file_name())
+				{
+					files.push(path);
+				}
+			}
+		}
+	}
+	files.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+	files
+}
+
+/// Return the names of the blacklisted modules in the configuration files.
+pub fn blacklisted_modules(files: &[PathBuf]) -> HashSet<String> {
+	files
+		.iter()
+		.filter_map(|path| fs::read_to_string(Path::new(path)).ok())
+		.flat_map(|contents| {
+			contents
+				.lines()
+				.filter_map(|line| {
+					let mut words = line.split_whitespace();
+					match (words.next(), words.next()) {
+						(Some("blacklist"), Some(name)) => {
+							Some(modinfo::module_name(name))
+						}
+						_ => None,
+					}
+				})
+				.collect::<Vec<String>>()
+		})
+		.collect()
+}
+
+/// Parse the directives in the configuration files.
+///
+/// Lines that are disabled by kmon are returned as disabled entries.
+pub fn config_entries(files: &[PathBuf]) -> Vec<ConfigEntry> {
+	let mut entries = Vec::new();
+	for path in files {
+		let contents = fs::read_to_string(path).unwrap_or_default();
+		let mut lines = contents.lines().enumerate();
+		while let Some((index, line)) = lines.next() {
+			let disabled = line.starts_with(DISABLED_PREFIX);
+			let mut text = line.trim_start_matches(DISABLED_PREFIX).to_string();
+			let mut line_count = 1;
+			// Join the lines that end with a backslash.
+			while text.ends_with('\\') {
+				text.pop();
+				match lines.next() {
+					Some((_, line)) => {
+						text += " ";
+						text += line.trim_start_matches(DISABLED_PREFIX);
+						line_count += 1;
+					}
+					None => break,
+				}
+			}
+			let words = text.split_whitespace().collect::<Vec<&str>>();
+			let (module, args) = match words.as_slice() {
+				["alias", wildcard, module, ..] => (*module, wildcard.to_string()),
+				["blacklist" | "install" | "remove" | "options" | "softdep", module, args @ ..] => {
+					(*module, args.join(" "))
+				}
+				_ => continue,
+			};
+			entries.push(ConfigEntry {
+				path: path.to_path_buf(),
+				line: index + 1,
+				line_count,
+				directive: words[0].to_string(),
+				module: modinfo::module_name(module),
+				args,
+				disabled,
+			});
+		}
+	}
+	entries
+}
+
+/// Contents that are written to a file by a command
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FileWrite {
+	pub path: PathBuf,
+	pub contents: String,
+}
+
+impl FileWrite {
+	/// Create a new write of the contents to the given file.
+	pub fn new(path: &Path, contents: String) -> Self {
+		Self {
+			path: path.to_path_buf(),
+			contents,
+		}
+	}
+
+	/// Return the command that writes the contents to the file atomically.
+	pub fn command(&self) -> String {
+		let temp_path = format!("{}.kmon-tmp", self.path.display());
+		format!(
+			"mkdir -p {dir} && printf '%s' {contents} > {temp} && mv {temp} {path}",
+			dir = util::shell_quote(
+				&self
+					.path
+					.parent()
+					.unwrap_or(Path::new("/"))
+					.to_string_lossy()
+			),
+			contents = util::shell_quote(&self.contents),
+			temp = util::shell_quote(&temp_path),
+			path = util::shell_quote(&self.path.to_string_lossy()),
+		)
+	}
+}
+
+/// Return the command that performs the given writes in order.
+pub fn write_commands(writes: &[FileWrite]) -> String {
+	writes
+		.iter()
+		.map(FileWrite::command)
+		.collect::<Vec<String>>()
+		.join(" && ")
+}
+
+/// Return the contents with the lines of the given entries edited.
+///
+/// Lines are removed if the file is owned by kmon, otherwise they are
+/// commented out or uncommented if they are already disabled.
+fn edit_entries(contents: &str, entries: &[&ConfigEntry], owned: bool) -> String {
+	let mut lines = Vec::new();
+	for (index, line) in contents.lines().enumerate() {
+		let edited = entries
+			.iter()
+			.any(|v| (v.line..v.line + v.line_count).contains(&(index + 1)));
+		match line.strip_prefix(DISABLED_PREFIX) {
+			_ if !edited => lines.push(line.to_string()),
+			_ if owned => {}
+			Some(line) => lines.push(line.to_string()),
+			None => lines.push(format!("{DISABLED_PREFIX}{line}")),
+		}
+	}
+	lines.into_iter().map(|v| v + "\n").collect()
+}
+
+/// Return the write that edits the given entries of a file.
+///
+/// Files outside of '/etc/modprobe.d' are overridden by a file with the
+/// same name in there instead of being edited.
+fn edit_write(path: &Path, entries: &[&ConfigEntry]) -> FileWrite {
+	let config_dir = Path::new(CONFIG_DIRS[0]);
+	let owned = path == Path::new(KMON_CONFIG);
+	let target = if path.parent() == Some(config_dir) {
+		path.to_path_buf()
+	} else {
+		config_dir.join(path.file_name().unwrap_or_default())
+	};
+	FileWrite::new(
+		&target,
+		edit_entries(
+			&fs::read_to_string(path).unwrap_or_default(),
+			entries,
+			owned,
+		),
+	)
+}
+
+/// Return the write that disables, enables or removes the entry at the
+/// given source.
+pub fn toggle_write(entries: &[ConfigEntry], source: &str) -> Option<FileWrite> {
+	entries
+		.iter()
+		.find(|entry| entry.source() == source)
+		.map(|entry| edit_write(&entry.path, &[entry]))
+}
+
+/// Return the writes that remove the blacklist entries of a module.
+///
+/// Nothing is written if the module is not blacklisted.
+pub fn unblacklist_writes(entries: &[ConfigEntry], module: &str) -> Vec<FileWrite> {
+	let mut files = BTreeMap::<&Path, Vec<&ConfigEntry>>::new();
+	for entry in entries {
+		if entry.module == module && entry.is_blacklist() && !entry.disabled {
+			files.entry(&entry.path).or_default().push(entry);
+		}
+	}
+	files
+		.into_iter()
+		.map(|(path, entries)| edit_write(path, &entries))
+		.collect()
+}
+
+/// Return the write that blacklists a module in the kmon configuration.
+pub fn blacklist_write(module: &str) -> FileWrite {
+	FileWrite::new(
+		Path::new(KMON_CONFIG),
+		blacklist(&fs::read_to_string(KMON_CONFIG).unwrap_or_default(), module),
+	)
+}
+
+/// Add the blacklist entries of a module to the given configuration contents.
+pub fn blacklist(contents: &str, module: &str) -> String {
+	let entries = [
+		format!("blacklist {module}"),
+		format!("install {module} /bin/false"),
+	];
+	let mut contents = contents
+		.lines()
+		.filter(|line| !entries.iter().any(|v| v == line.trim()))
+		.map(|line| format!("{line}\n"))
+		.collect::<String>();
+	entries.iter().for_each(|v| contents += &format!("{v}\n"));
+	contents
+}
+
+/// Replace the options of a module in the given configuration contents.
+///
+/// Options of the module are removed if the given options are empty.
+pub fn set_options(contents: &str, module: &str, options: &str) -> String {
+	let mut contents = contents
+		.lines()
+		.filter(|line| {
+			let mut words = line.split_whitespace();
+			!(words.next() == Some("options")
+				&& words.next().map(modinfo::module_name).as_deref() == Some(module))
+		})
+		.map(|line| format!("{line}\n"))
+		.collect::<String>();
+	if !options.trim().is_empty() {
+		contents += &format!("options {module} {}\n", options.trim());
+	}
+	contents
+}
+
+/// Return the write that sets the options of a module in the kmon
+/// configuration.
+pub fn options_write(module: &str, options: &str) -> FileWrite {
+	FileWrite::new(
+		Path::new(KMON_CONFIG),
+		set_options(
+			&fs::read_to_string(KMON_CONFIG).unwrap_or_default(),
+			module,
+			options,
+		),
+	)
+}
+
+/// Return the lines that are added to the contents of a file.
+pub fn added_lines(old: &str, new: &str) -> Vec<String> {
+	util::diff_lines(old, new)
+		.into_iter()
+		.filter_map(|line| line.strip_prefix('+').map(String::from))
+		.collect()
+}
+
+/// Return the write that removes the given lines from a file.
+///
+/// Only the last occurrence of each line is removed.
+pub fn remove_lines_write(path: &Path, lines: &[String]) -> FileWrite {
+	let mut contents = fs::read_to_string(path)
+		.unwrap_or_default()
+		.lines()
+		.map(String::from)
+		.collect::<Vec<String>>();
+	for line in lines {
+		if let Some(index) = contents.iter().rposition(|v| v == line) {
+			contents.remove(index);
+		}
+	}
+	FileWrite::new(
+		path,
+		contents
+			.iter()
+			.map(|line| format!("{line}\n"))
+			.collect::<String>(),
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::error::Error;
+	#[test]
+	fn test_blacklisted_modules() -> Result<(), Box<dyn Error>> {
+		let dir = util::temp_path("modprobe");
+		fs::create_dir_all(dir.join("etc"))?;
+		fs::create_dir_all(dir.join("lib"))?;
+		fs::write(dir.join("etc/a.conf"), "blacklist pcspkr\n")?;
+		fs::write(dir.join("lib/a.conf"), "blacklist nouveau\n")?;
+		fs::write(
+			dir.join("lib/b.conf"),
+			"# blacklist x\nblacklist snd-pcsp\n",
+		)?;
+		fs::write(dir.join("lib/c.txt"), "blacklist ignored\n")?;
+		let config_dirs = [
+			dir.join("etc").to_string_lossy().to_string(),
+			dir.join("lib").to_string_lossy().to_string(),
+		];
+		let config_dirs = config_dirs.iter().map(String::as_str).collect::<Vec<_>>();
+		let files = config_files(&config_dirs);
+		assert_eq!(vec![dir.join("etc/a.conf"), dir.join("lib/b.conf")], files);
+		assert_eq!(
+			HashSet::from([String::from("pcspkr"), String::from("snd_pcsp")]),
+			blacklisted_modules(&files)
+		);
+		fs::write(
+			dir.join("lib/b.conf"),
+			"# kmon: blacklist snd-pcsp\noptions snd slots=\\\n  a,b\nalias pci:v* snd\n",
+		)?;
+		let entries = config_entries(&files);
+		assert_eq!(4, entries.len());
+		assert_eq!("a.conf:1", entries[0].source());
+		assert!(entries[1].disabled && entries[1].is_blacklist());
+		assert_eq!(
+			("options", "snd", "slots= a,b", 2),
+			(
+				entries[2].directive.as_str(),
+				entries[2].module.as_str(),
+				entries[2].args.as_str(),
+				entries[2].line_count
+			)
+		);
+		assert_eq!(("snd", "pci:v*"), (&*entries[3].module, &*entries[3].args));
+		assert_eq!(
+			"blacklist snd-pcsp\n# kmon: options snd slots=\\\n# kmon:   a,b\n\
+			alias pci:v* snd\n",
+			edit_entries(
+				&fs::read_to_string(dir.join("lib/b.conf"))?,
+				&[&entries[1], &entries[2]],
+				false
+			)
+		);
+		assert_eq!("", edit_entries("blacklist pcspkr", &[&entries[0]], true));
+		assert!(toggle_write(&entries, "b.conf:2")
+			.is_some_and(|v| v.path == Path::new("/etc/modprobe.d/b.conf")));
+		assert!(unblacklist_writes(&entries, "snd_pcsp").is_empty());
+		assert_eq!(1, unblacklist_writes(&entries, "pcspkr").len());
+		let writes = [
+			FileWrite::new(&dir.join("x/y.conf"), String::from("it's\n")),
+			FileWrite::new(&dir.join("x/z.conf"), String::from("a b\n")),
+		];
+		crate::util::exec_cmd("sh", &["-c", &write_commands(&writes)])?;
+		assert_eq!("it's\n", fs::read_to_string(dir.join("x/y.conf"))?);
+		assert_eq!("a b\n", fs::read_to_string(dir.join("x/z.conf"))?);
+		assert!(blacklist_write("pcspkr")
+			.contents
+			.contains("blacklist pcspkr\n"));
+		assert_eq!(
+			"blacklist snd\noptions snd index=1\n",
+			set_options("options snd index=0\nblacklist snd\n", "snd", "index=1")
+		);
+		assert_eq!(
+			"",
+			set_options("options snd-hda-intel a=1\n", "snd_hda_intel", "")
+		);
+		assert!(options_write("snd", "a=1")
+			.contents
+			.contains("options snd a=1\n"));
+		assert!(!options_write("snd", "").contents.contains("options snd"));
+		assert_eq!(
+			vec![String::from("install snd /bin/false")],
+			added_lines(
+				"blacklist snd\n",
+				"blacklist snd\ninstall snd /bin/false\n"
+			)
+		);
+		fs::write(dir.join("x/y.conf"), "a\nb\na\n")?;
+		let write = remove_lines_write(&dir.join("x/y.conf"), &[String::from("a")]);
+		crate::util::exec_cmd("sh", &["-c", &write.command()])?;
+		assert_eq!("a\nb\n", fs::read_to_string(dir.join("x/y.conf"))?);
+		fs::remove_dir_all(dir)?;
+		Ok(())
+	}
+}
@@ -0,0 +1,290 @@
+use crate::kernel::modinfo::ModuleInfo;
+use crate::kernel::modprobe::{self, ConfigEntry, FileWrite};
+use crate::style::Style;
+use crate::util;
+use ratatui::style::Modifier;
+use ratatui::text::{Line, Span, Text};
+use std::fs;
+use std::path::Path;
+
+/// Parameter of a kernel module in the options editor
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Parameter {
+	pub name: String,
+	pub desc: String,
+	pub param_type: String,
+	pub configured: Option<String>,
+	pub value: Option<String>,
+	pub source: Option<String>,
+}
+
+/// Editor of the options that are set for a module at boot
+pub struct OptionsEditor {
+	pub module: String,
+	pub params: Vec<Parameter>,
+	pub selected: usize,
+	pub input: Option<String>,
+}
+
+impl OptionsEditor {
+	/// Create a new options editor for the given module.
+	pub fn new(module: &str) -> Self {
+		let info = ModuleInfo::read(module).unwrap_or_default();
+		Self::with_entries(
+			module,
+			&info,
+			&modprobe::config_entries(&modprobe::config_files(
+				modprobe::CONFIG_DIRS,
+			)),
+		)
+	}
+
+	/// Create a new options editor using the given information and entries.
+	fn with_entries(
+		module: &str,
+		info: &ModuleInfo,
+		entries: &[ConfigEntry],
+	) -> Self {
+		let mut params = info
+			.parameters()
+			.into_iter()
+			.map(|(name, desc, param_type)| Parameter {
+				name: name.to_string(),
+				desc: desc.unwrap_or_default().trim().to_string(),
+				param_type: param_type.unwrap_or_default().to_string(),
+				..Parameter::default()
+			})
+			.collect::<Vec<Parameter>>();
+		// Later options override the earlier ones as in modprobe.
+		for entry in entries.iter().filter(|entry| {
+			entry.directive == "options" && entry.module == module && !entry.disabled
+		}) {
+			for option in entry.args.split_whitespace() {
+				let (name, value) = option.split_once('=').unwrap_or((option, ""));
+				let index = params
+					.iter()
+					.position(|v| v.name == name)
+					.unwrap_or_else(|| {
+						params.push(Parameter {
+							name: name.to_string(),
+							..Parameter::default()
+						});
+						params.len() - 1
+					});
+				params[index].configured = Some(value.to_string());
+				params[index].value = Some(value.to_string());
+				// Values in the other files are not changed by the editor.
+				params[index].source = (entry.path
+					!= Path::new(modprobe::KMON_CONFIG))
+				.then(|| entry.source());
+			}
+		}
+		Self {
+			module: module.to_string(),
+			params,
+			selected: 0,
+			input: None,
+		}
+	}
+
+	/// Select the next parameter.
+	pub fn next(&mut self) {
+		if !self.params.is_empty() {
+			self.selected = (self.selected + 1) % self.params.len();
+		}
+	}
+
+	/// Select the previous parameter.
+	pub fn previous(&mut self) {
+		if !self.params.is_empty() {
+			self.selected = self
+				.selected
+				.checked_sub(1)
+				.unwrap_or(self.params.len() - 1);
+		}
+	}
+
+	/// Return the line of the selected parameter in the rendered text.
+	pub fn selected_line(&self) -> usize {
+		self.params[..self.selected.min(self.params.len())]
+			.iter()
+			.map(|param| if param.desc.is_empty() { 1 } else { 2 })
+			.sum()
+	}
+
+	/// Start editing the value of the selected parameter, or set the
+	/// edited value if it is already being edited.
+	///
+	/// Values with spaces are not set and the values that are configured in
+	/// the other files are not edited.
+	pub fn edit(&mut self) {
+		let Some(param) = self.params.get_mut(self.selected) else {
+			return;
+		};
+		match self.input.take() {
+			Some(input) if input.trim().is_empty() => param.value = None,
+			Some(input) if input.trim().contains(char::is_whitespace) => {
+				self.input = Some(input)
+			}
+			Some(input) => param.value = Some(input.trim().to_string()),
+			None if param.source.is_some() => {}
+			None => self.input = Some(param.value.clone().unwrap_or_default()),
+		}
+	}
+
+	/// Clear the value of the selected parameter.
+	pub fn clear(&mut self) {
+		if let Some(param) = self
+			.params
+			.get_mut(self.selected)
+			.filter(|param| param.source.is_none())
+		{
+			param.value = None;
+		}
+	}
+
+	/// Return the options of the module in the modprobe format.
+	///
+	/// Values that are configured in the other files are not included.
+	pub fn options(&self) -> String {
+		self.params
+			.iter()
+			.filter(|param| param.source.is_none())
+			.filter_map(|param| match param.value.as_deref() {
+				Some("") => Some(param.name.to_string()),
+				Some(value) => Some(format!("{}={value}", param.name)),
+				None => None,
+			})
+			.collect::<Vec<String>>()
+			.join(" ")
+	}
+
+	/// Return the contents of the kmon configuration before and after saving.
+	fn contents(&self) -> (String, String) {
+		let contents = fs::read_to_string(modprobe::KMON_CONFIG).unwrap_or_default();
+		let new_contents =
+			modprobe::set_options(&contents, &self.module, &self.options());
+		(contents, new_contents)
+	}
+
+	/// Return the write that saves the options.
+	pub fn write(&self) -> FileWrite {
+		modprobe::options_write(&self.module, &self.options())
+	}
+
+	/// Return the difference of the kmon configuration after saving.
+	pub fn diff(&self) -> Vec<String> {
+		let (contents, new_contents) = self.contents();
+		util::diff_lines(&contents, &new_contents)
+	}
+
+	/// Render the editor as styled and raw text.
+	pub fn render(&self, style: &Style) -> (Text<'static>, String) {
+		let mut lines = Vec::new();
+		let mut raw_lines = Vec::new();
+		if self.params.is_empty() {
+			let message = format!("{} has no parameters", self.module);
+			lines.push(Line::from(Span::styled(message.to_string(), style.colored)));
+			raw_lines.push(message);
+		}
+		for (index, param) in self.params.iter().enumerate() {
+			let value = match (&self.input, &param.value) {
+				(Some(input), _) if index == self.selected => format!("{input}_"),
+				(_, Some(value)) => value.to_string(),
+				(_, None) => String::from("-"),
+			};
+			let modified = match &param.source {
+				Some(source) => format!(" (set in {source})"),
+				None if param.value != param.configured => {
+					String::from(" (modified)")
+				}
+				None => String::new(),
+			};
+			let title = if param.param_type.is_empty() {
+				param.name.to_string()
+			} else {
+				format!("{} ({})", param.name, param.param_type)
+			};
+			lines.push(Line::from(vec![
+				Span::styled(
+					format!("{title}:"),
+					if index == self.selected {
+						style.colored.add_modifier(Modifier::REVERSED)
+					} else {
+						style.colored
+					},
+				),
+				Span::styled(format!(" {value}"), style.default),
+				Span::styled(modified.to_string(), style.bold),
+			]));
+			raw_lines.push(format!("{title}: {value}{modified}"));
+			if !param.desc.is_empty() {
+				lines.push(Line::from(Span::styled(
+					format!("  {}", param.desc),
+					style.default,
+				)));
+				raw_lines.push(format!("  {}", param.desc));
+			}
+		}
+		(Text::from(lines), raw_lines.join("\n"))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use clap::ArgMatches;
+	use std::path::PathBuf;
+	#[test]
+	fn test_options_editor() {
+		let info = ModuleInfo {
+			filename: String::from("snd.ko"),
+			fields: vec![
+				(String::from("parm"), String::from("index:Index value")),
+				(String::from("parmtype"), String::from("index:int")),
+				(String::from("parmtype"), String::from("debug:bool")),
+			],
+		};
+		let entry = ConfigEntry {
+			path: PathBuf::from(modprobe::KMON_CONFIG),
+			line: 1,
+			line_count: 1,
+			directive: String::from("options"),
+			module: String::from("snd"),
+			args: String::from("index=-2 slots=2"),
+			disabled: false,
+		};
+		let other_entry = ConfigEntry {
+			path: PathBuf::from("/etc/modprobe.d/snd.conf"),
+			args: String::from("debug=1"),
+			..entry.clone()
+		};
+		let mut editor =
+			OptionsEditor::with_entries("snd", &info, &[entry, other_entry]);
+		assert_eq!(3, editor.params.len());
+		assert_eq!("index=-2 slots=2", editor.options());
+		editor.edit();
+		editor.input = Some(String::from("1 2"));
+		editor.edit();
+		assert_eq!(Some(String::from("1 2")), editor.input);
+		editor.input = Some(String::from("1"));
+		editor.edit();
+		editor.next();
+		editor.edit();
+		assert_eq!(None, editor.input);
+		editor.clear();
+		editor.next();
+		editor.clear();
+		assert_eq!("index=1", editor.options());
+		let raw_text = editor.render(&Style::new(&ArgMatches::default())).1;
+		assert!(raw_text.starts_with("index (int): 1 (modified)\n  Index value\n"));
+		assert!(raw_text.contains("debug (bool): 1 (set in snd.conf:1)\n"));
+		assert!(raw_text.ends_with("slots: - (modified)"));
+		editor.previous();
+		editor.previous();
+		assert_eq!(0, editor.selected);
+		editor.next();
+		editor.next();
+		assert_eq!(3, editor.selected_line());
+	}
+}
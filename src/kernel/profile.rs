@@ -0,0 +1,373 @@
+use crate::kernel::audit::{AuditLog, AuditRecord};
+use crate::kernel::cmd::{Escalation, ModuleCommand, ProtectedModules};
+use crate::kernel::graph::ModuleGraph;
+use crate::kernel::modinfo;
+use crate::kernel::modprobe;
+use crate::kernel::sysfs;
+use clap::ArgMatches;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{self, Stdio};
+
+/// Declarative state of the kernel modules
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+	#[serde(default)]
+	pub load: BTreeMap<String, BTreeMap<String, toml::Value>>,
+	#[serde(default)]
+	pub unload: Vec<String>,
+	#[serde(default)]
+	pub blacklist: Vec<String>,
+}
+
+/// Step of the plan for applying a profile
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlanStep {
+	pub command: ModuleCommand,
+	pub module: String,
+	pub options: Vec<(String, String)>,
+}
+
+impl PlanStep {
+	/// Return the options as the arguments of modprobe.
+	fn option_args(&self) -> Vec<String> {
+		self.options
+			.iter()
+			.map(|(param, value)| format!("{param}={value}"))
+			.collect()
+	}
+
+	/// Return the command of the step.
+	///
+	/// Commands are built when they are executed since the blacklist
+	/// command depends on the current configuration.
+	pub fn cmd(&self) -> String {
+		match self.command {
+			ModuleCommand::Load => {
+				ModuleCommand::load_command_with(&self.module, self.option_args())
+			}
+			ModuleCommand::Reload => format!(
+				"{} && {}",
+				ModuleCommand::Unload.get(&self.module).cmd,
+				ModuleCommand::load_command_with(&self.module, self.option_args())
+			),
+			command => command.get(&self.module).cmd,
+		}
+	}
+}
+
+impl Display for PlanStep {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} {}", self.command.name(), self.module)?;
+		if !self.options.is_empty() {
+			write!(f, " ({})", self.option_args().join(" "))?;
+		}
+		Ok(())
+	}
+}
+
+impl Profile {
+	/// Read the profile from a TOML file.
+	pub fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+		let mut profile: Self = toml::from_str(&fs::read_to_string(path)?)?;
+		profile.load = profile
+			.load
+			.into_iter()
+			.map(|(name, params)| (modinfo::module_name(&name), params))
+			.collect();
+		for modules in [&mut profile.unload, &mut profile.blacklist] {
+			*modules = modules.iter().map(|v| modinfo::module_name(v)).collect();
+		}
+		if let Some(name) = profile
+			.unload
+			.iter()
+			.chain(&profile.blacklist)
+			.find(|name| profile.load.contains_key(*name))
+		{
+			return Err(format!("{name} is both loaded and removed").into());
+		}
+		Ok(profile)
+	}
+
+	/// Return the value of a parameter in the modprobe format.
+	fn option_value(value: &toml::Value) -> String {
+		match value {
+			toml::Value::String(v) => v.to_string(),
+			toml::Value::Boolean(v) => String::from(if *v { "Y" } else { "N" }),
+			toml::Value::Array(values) => values
+				.iter()
+				.map(Self::option_value)
+				.collect::<Vec<String>>()
+				.join(","),
+			v => v.to_string(),
+		}
+	}
+
+	/// Return the plan for applying the profile to the running kernel.
+	pub fn plan(&self) -> Result<Vec<PlanStep>, Box<dyn Error>> {
+		let mut builtin_modules = modinfo::builtin_modules(&modinfo::modules_dir());
+		builtin_modules.sort();
+		self.plan_with(
+			&ModuleGraph::loaded()?,
+			&builtin_modules,
+			&modprobe::blacklisted_modules(&modprobe::config_files(
+				modprobe::CONFIG_DIRS,
+			)),
+			Path::new(sysfs::SYS_MODULE_DIR),
+		)
+	}
+
+	/// Return the plan for applying the profile to the given state.
+	///
+	/// Modules are unloaded in the order of removal, then blacklisted, and
+	/// then loaded or reloaded if their parameters differ.
+	fn plan_with(
+		&self,
+		graph: &ModuleGraph,
+		builtin_modules: &[String],
+		blacklisted_modules: &HashSet<String>,
+		sys_module_dir: &Path,
+	) -> Result<Vec<PlanStep>, Box<dyn Error>> {
+		let step = |command, module: &str, options: &[(String, String)]| PlanStep {
+			command,
+			module: module.to_string(),
+			options: options.to_vec(),
+		};
+		let mut plan = Vec::new();
+		for name in &self.unload {
+			if builtin_modules.contains(name) {
+				return Err(format!("{name} is built into the kernel").into());
+			}
+		}
+		for name in graph.removal_order(&self.unload) {
+			if graph.nodes.contains_key(&name) {
+				plan.push(step(ModuleCommand::Unload, &name, &[]));
+			}
+		}
+		for name in &self.blacklist {
+			if !blacklisted_modules.contains(name) {
+				plan.push(step(ModuleCommand::Blacklist, name, &[]));
+			}
+		}
+		for (name, params) in &self.load {
+			let params = params
+				.iter()
+				.map(|(param, value)| (param.to_string(), Self::option_value(value)))
+				.collect::<Vec<(String, String)>>();
+			if builtin_modules.contains(name) {
+				continue;
+			} else if !graph.nodes.contains_key(name) {
+				plan.push(step(ModuleCommand::Load, name, &params));
+			} else {
+				// Parameters that are not readable in sysfs are not compared.
+				let current = sysfs::module_parameters(sys_module_dir, name);
+				if params.iter().any(|(param, value)| {
+					current.iter().any(|(k, v)| k == param && v != value)
+				}) {
+					plan.push(step(ModuleCommand::Reload, name, &params));
+				}
+			}
+		}
+		Ok(plan)
+	}
+}
+
+/// Return the steps that change a protected module along with the reasons.
+///
+/// Modules that are allowed for the run are skipped.
+fn protected_steps<'a>(
+	plan: &'a [PlanStep],
+	protected: &'a ProtectedModules,
+	allowed: &[String],
+) -> Vec<(&'a PlanStep, &'a str)> {
+	plan.iter()
+		.filter(|step| !allowed.contains(&step.module))
+		.filter_map(|step| {
+			protected
+				.requires_confirmation(step.command, &step.module)
+				.map(|reason| (step, reason))
+		})
+		.collect()
+}
+
+/// Read an answer from the standard input after showing the prompt.
+fn prompt(message: &str) -> Result<String, Box<dyn Error>> {
+	print!("{message}");
+	io::stdout().flush()?;
+	let mut answer = String::new();
+	io::stdin().read_line(&mut answer)?;
+	Ok(answer.trim().to_string())
+}
+
+/// Apply the profile of the `apply` subcommand after showing its plan.
+///
+/// Plan is applied without a confirmation if `--yes` is given. Steps that
+/// change a protected module require typing its name unless the module is
+/// allowed with `--allow-protected`.
+pub fn apply(args: &ArgMatches, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+	let path = matches
+		.get_one::<String>("profile")
+		.ok_or("profile is not given")?;
+	let plan = Profile::read(Path::new(path))?.plan()?;
+	if plan.is_empty() {
+		println!("Nothing to apply, the modules match the profile.");
+		return Ok(());
+	}
+	let protected = ProtectedModules::new(args);
+	let allowed = matches
+		.get_many::<String>("allow-protected")
+		.unwrap_or_default()
+		.map(|v| modinfo::module_name(v))
+		.collect::<Vec<String>>();
+	let protected_steps = protected_steps(&plan, &protected, &allowed);
+	println!("Plan:");
+	for step in &plan {
+		match protected_steps.iter().find(|(v, _)| *v == step) {
+			Some((_, reason)) => println!("  {step} [protected: {reason}]"),
+			None => println!("  {step}"),
+		}
+	}
+	if args.try_get_one::<bool>("dry-run").ok().flatten() == Some(&true) {
+		return Ok(());
+	} else if args.try_get_one::<bool>("read-only").ok().flatten() == Some(&true) {
+		return Err("profiles cannot be applied in read-only mode".into());
+	}
+	let protected_names = protected_steps
+		.iter()
+		.map(|(step, _)| step.module.to_string())
+		.collect::<Vec<String>>();
+	if !protected_names.is_empty() {
+		if matches.get_flag("yes") {
+			return Err(format!(
+				"protected modules are not changed without --allow-protected: {}",
+				protected_names.join(",")
+			)
+			.into());
+		}
+		let names = protected_names.join(" ");
+		if prompt(&format!("Type '{names}' to apply the plan: "))? != names {
+			println!("Cancelled.");
+			return Ok(());
+		}
+	} else if !matches.get_flag("yes")
+		&& !matches!(
+			prompt("Apply the plan? [y/N]: ")?.as_str(),
+			"y" | "Y" | "yes"
+		) {
+		println!("Cancelled.");
+		return Ok(());
+	}
+	let escalation = Escalation::new(args);
+	let audit_log = AuditLog::new(args);
+	audit_log
+		.check()
+		.map_err(|e| format!("audit log is not writable: {e}"))?;
+	let mut failed = 0;
+	// Audit errors are reported after the plan since the steps are executed.
+	let mut audit_errors = Vec::new();
+	for step in &plan {
+		let cmd = step.cmd();
+		let args = escalation.command(&cmd, false);
+		let output = process::Command::new(&args[0])
+			.args(&args[1..])
+			.stdin(Stdio::inherit())
+			.output();
+		let record = AuditRecord::new(
+			step.command,
+			&step.module,
+			&step.option_args().join(" "),
+			&cmd,
+			&output,
+		);
+		if let Err(e) = audit_log.write(&record) {
+			audit_errors.push(format!("{step}: {e}"));
+		}
+		match output {
+			Ok(output) if output.status.success() => println!("{step}: ok"),
+			_ => {
+				failed += 1;
+				println!("{step}: failed ({})", record.stderr.replace('\n', " "));
+			}
+		}
+	}
+	for error in &audit_errors {
+		eprintln!("audit log failed for {error}");
+	}
+	if failed != 0 {
+		return Err(format!("{failed} of {} steps failed", plan.len()).into());
+	} else if !audit_errors.is_empty() {
+		return Err(format!("{} steps are not audited", audit_errors.len()).into());
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	#[test]
+	fn test_profile() -> Result<(), Box<dyn Error>> {
+		let dir = crate::util::temp_path("profile");
+		fs::create_dir_all(dir.join("snd_hda_intel/parameters"))?;
+		fs::write(dir.join("snd_hda_intel/parameters/index"), "-1\n")?;
+		fs::write(dir.join("snd_hda_intel/parameters/probe_only"), "N\n")?;
+		fs::write(
+			dir.join("profile.toml"),
+			"unload = [\"snd\", \"pcspkr\", \"snd-hda-intel-x\"]\n\
+			blacklist = [\"nouveau\", \"floppy\"]\n\
+			[load.snd-hda-intel]\nindex = 0\nprobe_only = false\n\
+			[load.nvme]\nio_queue = \"a b\"\n[load.ext4]\n",
+		)?;
+		let profile = Profile::read(&dir.join("profile.toml"))?;
+		assert_eq!(3, profile.load.len());
+		let graph = ModuleGraph::parse(
+			"snd_hda_intel_x 1024 0 - Live 0x0\n\
+			snd 1024 1 snd_hda_intel_x, Live 0x0\n\
+			snd_hda_intel 1024 0 - Live 0x0\n",
+		);
+		let plan = profile.plan_with(
+			&graph,
+			&[String::from("ext4")],
+			&HashSet::from([String::from("floppy")]),
+			&dir,
+		)?;
+		assert_eq!(
+			vec![
+				"unload snd_hda_intel_x",
+				"unload snd",
+				"blacklist nouveau",
+				"load nvme (io_queue=a b)",
+				"reload snd_hda_intel (index=0 probe_only=N)",
+			],
+			plan.iter().map(|v| v.to_string()).collect::<Vec<String>>()
+		);
+		let mut protected = ProtectedModules::default();
+		protected.add("snd", "test");
+		protected.add("nvme", "test");
+		assert_eq!(
+			vec![(&plan[1], "test")],
+			protected_steps(&plan, &protected, &[])
+		);
+		assert!(
+			protected_steps(&plan, &protected, &[String::from("snd")]).is_empty()
+		);
+		assert_eq!(
+			"modprobe nvme 'io_queue=a b' || insmod nvme.ko 'io_queue=a b'",
+			plan[3].cmd()
+		);
+		assert!(plan[4]
+			.cmd()
+			.ends_with("insmod snd_hda_intel.ko 'index=0' 'probe_only=N'"));
+		fs::write(dir.join("profile.toml"), "unload = [\"a\"]\n[load.a]\n")?;
+		assert!(Profile::read(&dir.join("profile.toml")).is_err());
+		fs::write(dir.join("profile.toml"), "load = 1\n")?;
+		assert!(Profile::read(&dir.join("profile.toml")).is_err());
+		fs::remove_dir_all(dir)?;
+		Ok(())
+	}
+}
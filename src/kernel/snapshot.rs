@@ -0,0 +1,301 @@
+use crate::kernel::graph::ModuleGraph;
+use crate::kernel::sysfs;
+use clap::ArgMatches;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::Path;
+
+/// Marker of the modules that are loaded after the snapshot
+pub const ADDED_MARKER: &str = "[added]";
+
+/// Marker of the modules that are removed after the snapshot
+pub const REMOVED_MARKER: &str = "[removed]";
+
+/// Marker of the modules that are changed after the snapshot
+pub const CHANGED_MARKER: &str = "[changed]";
+
+/// State of a loaded module in a snapshot
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModuleState {
+	pub size: u64,
+	pub taint: String,
+	pub version: String,
+	pub parameters: BTreeMap<String, String>,
+}
+
+/// Snapshot of the loaded modules
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Snapshot {
+	pub kernel: String,
+	pub modules: BTreeMap<String, ModuleState>,
+}
+
+impl Snapshot {
+	/// Create a snapshot of the running kernel.
+	pub fn live() -> Result<Self, Box<dyn Error>> {
+		Ok(Self::capture(
+			&ModuleGraph::loaded()?,
+			Path::new(sysfs::SYS_MODULE_DIR),
+			fs::read_to_string("/proc/sys/kernel/osrelease")
+				.unwrap_or_default()
+				.trim(),
+		))
+	}
+
+	/// Create a snapshot of the loaded modules in the given graph.
+	///
+	/// Source version is recorded for the modules without a version.
+	fn capture(graph: &ModuleGraph, sys_module_dir: &Path, kernel: &str) -> Self {
+		Self {
+			kernel: kernel.to_string(),
+			modules: graph
+				.nodes
+				.iter()
+				.map(|(name, node)| {
+					let module_dir = sys_module_dir.join(name);
+					let version = ["version", "srcversion"]
+						.iter()
+						.find_map(|v| fs::read_to_string(module_dir.join(v)).ok())
+						.unwrap_or_default();
+					let state = ModuleState {
+						size: node.size,
+						taint: node.taint.to_string(),
+						version: version.trim().to_string(),
+						parameters: sysfs::module_parameters(sys_module_dir, name)
+							.into_iter()
+							.collect(),
+					};
+					(name.to_string(), state)
+				})
+				.collect(),
+		}
+	}
+
+	/// Read a snapshot from a JSON file.
+	pub fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+		serde_json::from_str(&fs::read_to_string(path)?)
+			.map_err(|e| format!("invalid snapshot {}: {e}", path.display()).into())
+	}
+
+	/// Write the snapshot to a JSON file.
+	pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+		fs::write(path, serde_json::to_string_pretty(self)? + "\n")?;
+		Ok(())
+	}
+
+	/// Return the differences from the snapshot to the other snapshot.
+	pub fn diff(&self, other: &Self) -> SnapshotDiff {
+		let names = self
+			.modules
+			.keys()
+			.chain(other.modules.keys())
+			.collect::<BTreeSet<&String>>();
+		SnapshotDiff {
+			kernel: (self.kernel != other.kernel)
+				.then(|| (self.kernel.to_string(), other.kernel.to_string())),
+			changes: names
+				.into_iter()
+				.filter_map(|name| {
+					let (kind, details) =
+						match (self.modules.get(name), other.modules.get(name)) {
+							(None, Some(_)) => (ChangeKind::Added, Vec::new()),
+							(Some(_), None) => (ChangeKind::Removed, Vec::new()),
+							(Some(old), Some(new)) => {
+								(ChangeKind::Changed, Self::details(old, new))
+							}
+							(None, None) => return None,
+						};
+					(kind != ChangeKind::Changed || !details.is_empty()).then(|| {
+						ModuleChange {
+							kind,
+							module: name.to_string(),
+							details,
+						}
+					})
+				})
+				.collect(),
+		}
+	}
+
+	/// Return the changed parameters, version and taint of a module.
+	fn details(old: &ModuleState, new: &ModuleState) -> Vec<String> {
+		let value = |v: Option<&String>| {
+			v.filter(|v| !v.is_empty())
+				.map_or(String::from("-"), |v| v.to_string())
+		};
+		let mut details = old
+			.parameters
+			.keys()
+			.chain(new.parameters.keys())
+			.collect::<BTreeSet<&String>>()
+			.into_iter()
+			.filter(|param| old.parameters.get(*param) != new.parameters.get(*param))
+			.map(|param| {
+				format!(
+					"{param}: {} -> {}",
+					value(old.parameters.get(param)),
+					value(new.parameters.get(param))
+				)
+			})
+			.collect::<Vec<String>>();
+		for (field, old_value, new_value) in [
+			("version", &old.version, &new.version),
+			("taint", &old.taint, &new.taint),
+		] {
+			if old_value != new_value {
+				details.push(format!(
+					"{field}: {} -> {}",
+					value(Some(old_value)),
+					value(Some(new_value))
+				));
+			}
+		}
+		details
+	}
+}
+
+/// Kind of a module change between snapshots
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+	Added,
+	Removed,
+	Changed,
+}
+
+impl ChangeKind {
+	/// Return the marker of the change in the module table.
+	pub fn marker(&self) -> &'static str {
+		match self {
+			Self::Added => ADDED_MARKER,
+			Self::Removed => REMOVED_MARKER,
+			Self::Changed => CHANGED_MARKER,
+		}
+	}
+}
+
+/// Change of a module between snapshots
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModuleChange {
+	pub kind: ChangeKind,
+	pub module: String,
+	pub details: Vec<String>,
+}
+
+impl Display for ModuleChange {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		let sign = match self.kind {
+			ChangeKind::Added => '+',
+			ChangeKind::Removed => '-',
+			ChangeKind::Changed => '~',
+		};
+		write!(f, "{sign} {}", self.module)?;
+		if !self.details.is_empty() {
+			write!(f, " ({})", self.details.join(", "))?;
+		}
+		Ok(())
+	}
+}
+
+/// Differences between two snapshots
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SnapshotDiff {
+	pub kernel: Option<(String, String)>,
+	pub changes: Vec<ModuleChange>,
+}
+
+impl SnapshotDiff {
+	/// Check if the snapshots are the same.
+	pub fn is_empty(&self) -> bool {
+		self.kernel.is_none() && self.changes.is_empty()
+	}
+}
+
+impl Display for SnapshotDiff {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		if let Some((old, new)) = &self.kernel {
+			writeln!(f, "kernel: {old} -> {new}")?;
+		}
+		for change in &self.changes {
+			writeln!(f, "{change}")?;
+		}
+		Ok(())
+	}
+}
+
+/// Run the `save` and `diff` commands of the `snapshot` subcommand.
+///
+/// Snapshots are compared with the running kernel if the second file
+/// is not given.
+pub fn run(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+	match matches.subcommand() {
+		Some(("save", matches)) => {
+			let path = matches
+				.get_one::<String>("file")
+				.ok_or("file is not given")?;
+			let snapshot = Snapshot::live()?;
+			snapshot.save(Path::new(path))?;
+			println!("Saved {} modules to {path}", snapshot.modules.len());
+		}
+		Some(("diff", matches)) => {
+			let old = matches
+				.get_one::<String>("old")
+				.ok_or("file is not given")?;
+			let new = match matches.get_one::<String>("new") {
+				Some(path) => Snapshot::read(Path::new(path))?,
+				None => Snapshot::live()?,
+			};
+			let diff = Snapshot::read(Path::new(old))?.diff(&new);
+			if diff.is_empty() {
+				println!("No differences.");
+			} else {
+				print!("{diff}");
+			}
+		}
+		_ => {}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	#[test]
+	fn test_snapshot() -> Result<(), Box<dyn Error>> {
+		let dir = crate::util::temp_path("snapshot");
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(dir.join("snd/parameters"))?;
+		fs::write(dir.join("snd/parameters/index"), "0\n")?;
+		fs::write(dir.join("snd/srcversion"), "ABC\n")?;
+		let graph = ModuleGraph::parse(
+			"snd 2048 0 - Live 0x0\npcspkr 1024 0 - Live 0x0 (OE)\n",
+		);
+		let old = Snapshot::capture(&graph, &dir, "6.1.0");
+		old.save(&dir.join("snapshot.json"))?;
+		assert_eq!(old, Snapshot::read(&dir.join("snapshot.json"))?);
+		assert_eq!("ABC", old.modules["snd"].version);
+		assert_eq!("OE", old.modules["pcspkr"].taint);
+		assert!(old.diff(&old).is_empty());
+		fs::write(dir.join("snd/parameters/index"), "1\n")?;
+		fs::write(dir.join("snd/parameters/enable"), "Y\n")?;
+		fs::write(dir.join("snd/version"), "1.1\n")?;
+		let graph =
+			ModuleGraph::parse("snd 2048 0 - Live 0x0\nnvme 4096 0 - Live 0x0\n");
+		let diff = old.diff(&Snapshot::capture(&graph, &dir, "6.2.0"));
+		assert_eq!(
+			"kernel: 6.1.0 -> 6.2.0\n\
+			+ nvme\n\
+			- pcspkr\n\
+			~ snd (enable: - -> Y, index: 0 -> 1, version: ABC -> 1.1)\n",
+			diff.to_string()
+		);
+		fs::write(dir.join("snapshot.json"), "{\"modules\": []}")?;
+		assert!(Snapshot::read(&dir.join("snapshot.json")).is_err());
+		fs::remove_dir_all(dir)?;
+		Ok(())
+	}
+}
@@ -0,0 +1,177 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory of the modules in sysfs
+pub const SYS_MODULE_DIR: &str = "/sys/module";
+
+/// Return the names of the entries in a directory in sorted order.
+pub fn dir_entries(path: &Path) -> Vec<String> {
+	let mut entries = fs::read_dir(path)
+		.map(|entries| {
+			entries
+				.flatten()
+				.map(|entry| entry.file_name().to_string_lossy().to_string())
+				.collect::<Vec<String>>()
+		})
+		.unwrap_or_default();
+	entries.sort();
+	entries
+}
+
+/// Return the current parameter values of a module.
+pub fn module_parameters(
+	sys_module_dir: &Path,
+	name: &str,
+) -> Vec<(String, String)> {
+	let parameters_dir = sys_module_dir.join(name).join("parameters");
+	dir_entries(&parameters_dir)
+		.into_iter()
+		.filter_map(|param| {
+			// Parameters without read permission are skipped.
+			fs::read_to_string(parameters_dir.join(&param))
+				.ok()
+				.map(|value| (param, value.trim_end().to_string()))
+		})
+		.collect()
+}
+
+/// Return the modules that hold (depend on) the given module.
+pub fn module_holders(sys_module_dir: &Path, name: &str) -> Vec<String> {
+	dir_entries(&sys_module_dir.join(name).join("holders"))
+}
+
+/// Return the modules that the given module depends on.
+///
+/// Modules are found by checking which modules are held by the given module.
+pub fn module_dependencies(sys_module_dir: &Path, name: &str) -> Vec<String> {
+	dir_entries(sys_module_dir)
+		.into_iter()
+		.filter(|module| {
+			sys_module_dir
+				.join(module)
+				.join("holders")
+				.join(name)
+				.exists()
+		})
+		.collect()
+}
+
+/// Return the devices that are bound to the drivers of a module.
+///
+/// Devices are returned with the names of their drivers.
+pub fn module_devices(sys_module_dir: &Path, name: &str) -> Vec<(String, PathBuf)> {
+	let drivers_dir = sys_module_dir.join(name).join("drivers");
+	dir_entries(&drivers_dir)
+		.into_iter()
+		.flat_map(|driver| {
+			let driver_dir = drivers_dir.join(&driver);
+			dir_entries(&driver_dir)
+				.into_iter()
+				.filter(|entry| entry != "module")
+				.filter_map(|entry| {
+					let path = driver_dir.join(entry);
+					// Devices are the symbolic links in the driver directory.
+					fs::symlink_metadata(&path)
+						.ok()
+						.filter(|metadata| metadata.file_type().is_symlink())
+						.and_then(|_| fs::canonicalize(&path).ok())
+				})
+				.map(|device| (driver.to_string(), device))
+				.collect::<Vec<(String, PathBuf)>>()
+		})
+		.collect()
+}
+
+/// Return the network interfaces, block devices and sound cards of a device.
+///
+/// Interfaces are returned with their class names such as "net".
+pub fn device_interfaces(device: &Path) -> Vec<(String, String)> {
+	let mut interfaces = Vec::new();
+	let mut dirs = vec![(device.to_path_buf(), 0)];
+	while let Some((dir, depth)) = dirs.pop() {
+		for entry in dir_entries(&dir) {
+			let path = dir.join(&entry);
+			// Symbolic links are not followed to stay in the device tree.
+			if !fs::symlink_metadata(&path).is_ok_and(|v| v.is_dir()) {
+				continue;
+			}
+			match entry.as_str() {
+				"net" | "block" | "sound" => {
+					interfaces.extend(
+						dir_entries(&path)
+							.into_iter()
+							.filter(|v| entry != "sound" || v.starts_with("card"))
+							.map(|v| (entry.to_string(), v)),
+					);
+				}
+				_ if depth < 4 => dirs.push((path, depth + 1)),
+				_ => {}
+			}
+		}
+	}
+	interfaces.sort();
+	interfaces.dedup();
+	interfaces
+}
+
+/// Return the modules of the drivers that the device and its parents use.
+pub fn device_modules(device: &Path) -> Vec<String> {
+	let mut modules = Vec::new();
+	if let Ok(device) = fs::canonicalize(device) {
+		for path in device.ancestors() {
+			if let Some(module) = fs::read_link(path.join("driver").join("module"))
+				.ok()
+				.and_then(|v| v.file_name().map(|v| v.to_string_lossy().to_string()))
+			{
+				if !modules.contains(&module) {
+					modules.push(module);
+				}
+			}
+		}
+	}
+	modules
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::error::Error;
+	#[test]
+	fn test_module_parameters() -> Result<(), Box<dyn Error>> {
+		let dir = crate::util::temp_path("sysfs");
+		fs::create_dir_all(dir.join("ext4/parameters"))?;
+		fs::write(dir.join("ext4/parameters/mballoc_debug"), "0\n")?;
+		fs::write(dir.join("ext4/parameters/enable"), "Y\n")?;
+		assert_eq!(
+			vec![
+				(String::from("enable"), String::from("Y")),
+				(String::from("mballoc_debug"), String::from("0")),
+			],
+			module_parameters(&dir, "ext4")
+		);
+		assert!(module_parameters(&dir, "test").is_empty());
+		fs::create_dir_all(dir.join("jbd2/holders/ext4"))?;
+		fs::create_dir_all(dir.join("mbcache/holders/ext4"))?;
+		assert_eq!(vec![String::from("ext4")], module_holders(&dir, "jbd2"));
+		assert_eq!(
+			vec![String::from("jbd2"), String::from("mbcache")],
+			module_dependencies(&dir, "ext4")
+		);
+		fs::create_dir_all(dir.join("devices/pci/0000:00:1f.6/net/eno1"))?;
+		fs::create_dir_all(dir.join("drivers/e1000e"))?;
+		std::os::unix::fs::symlink(
+			dir.join("module/e1000e"),
+			dir.join("drivers/e1000e/module"),
+		)?;
+		std::os::unix::fs::symlink(
+			dir.join("drivers/e1000e"),
+			dir.join("devices/pci/0000:00:1f.6/driver"),
+		)?;
+		assert_eq!(
+			vec![String::from("e1000e")],
+			device_modules(&dir.join("devices/pci/0000:00:1f.6/net/eno1"))
+		);
+		fs::remove_dir_all(dir)?;
+		Ok(())
+	}
+}
@@ -0,0 +1,232 @@
+use crate::kernel::graph::ModuleGraph;
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::thread;
+use std::time::Duration;
+
+/// Multicast group of the uevents that are sent by the kernel
+const KERNEL_GROUP: u32 = 1;
+
+/// Maximum size of a uevent message
+const MESSAGE_SIZE: usize = 8192;
+
+/// Action of a module event
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModuleAction {
+	Load,
+	Unload,
+}
+
+impl Display for ModuleAction {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", format!("{self:?}").to_lowercase())
+	}
+}
+
+/// Load or unload event of a kernel module
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModuleEvent {
+	pub action: ModuleAction,
+	pub module: String,
+}
+
+impl ModuleEvent {
+	/// Parse a uevent message of the kernel.
+	///
+	/// Events of the other subsystems are ignored.
+	pub fn parse(message: &[u8]) -> Option<Self> {
+		let (mut action, mut subsystem, mut devpath) = (None, None, None);
+		// Message starts with a header such as "add@/module/snd".
+		for field in message
+			.split(|v| *v == 0)
+			.skip(1)
+			.filter_map(|v| std::str::from_utf8(v).ok())
+		{
+			match field.split_once('=') {
+				Some(("ACTION", value)) => action = Some(value),
+				Some(("SUBSYSTEM", value)) => subsystem = Some(value),
+				Some(("DEVPATH", value)) => devpath = Some(value),
+				_ => {}
+			}
+		}
+		if subsystem != Some("module") {
+			return None;
+		}
+		Some(Self {
+			action: match action? {
+				"add" => ModuleAction::Load,
+				"remove" => ModuleAction::Unload,
+				_ => return None,
+			},
+			module: devpath?.strip_prefix("/module/")?.to_string(),
+		})
+	}
+}
+
+/// Netlink socket that receives the uevents of the kernel
+pub struct UeventSocket {
+	fd: OwnedFd,
+}
+
+impl UeventSocket {
+	/// Open a socket that is subscribed to the kernel uevents.
+	pub fn open() -> io::Result<Self> {
+		let fd = unsafe {
+			libc::socket(
+				libc::AF_NETLINK,
+				libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+				libc::NETLINK_KOBJECT_UEVENT,
+			)
+		};
+		if fd < 0 {
+			return Err(io::Error::last_os_error());
+		}
+		let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+		let mut address: libc::sockaddr_nl = unsafe { mem::zeroed() };
+		address.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+		address.nl_groups = KERNEL_GROUP;
+		if unsafe {
+			libc::bind(
+				fd.as_raw_fd(),
+				&address as *const libc::sockaddr_nl as *const libc::sockaddr,
+				mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+			)
+		} < 0
+		{
+			return Err(io::Error::last_os_error());
+		}
+		Ok(Self { fd })
+	}
+
+	/// Wait for the next uevent and return it if it is a module event.
+	///
+	/// Messages that are not sent by the kernel are ignored.
+	pub fn receive(&self) -> io::Result<Option<ModuleEvent>> {
+		let mut message = [0; MESSAGE_SIZE];
+		let mut address: libc::sockaddr_nl = unsafe { mem::zeroed() };
+		let mut address_len = mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t;
+		let len = unsafe {
+			libc::recvfrom(
+				self.fd.as_raw_fd(),
+				message.as_mut_ptr() as *mut libc::c_void,
+				message.len(),
+				0,
+				&mut address as *mut libc::sockaddr_nl as *mut libc::sockaddr,
+				&mut address_len,
+			)
+		};
+		if len < 0 {
+			return Err(io::Error::last_os_error());
+		}
+		Ok((address.nl_pid == 0)
+			.then(|| ModuleEvent::parse(&message[..len as usize]))
+			.flatten())
+	}
+}
+
+/// Watcher of the loaded modules for polling `/proc/modules`
+#[derive(Clone, Debug, Default)]
+pub struct ModuleWatcher {
+	loaded: Option<BTreeSet<String>>,
+}
+
+impl ModuleWatcher {
+	/// Return the modules that are loaded or unloaded since the last update.
+	///
+	/// Modules that are loaded at the first update are not returned.
+	pub fn update(&mut self, graph: &ModuleGraph) -> Vec<ModuleEvent> {
+		let loaded = graph.nodes.keys().cloned().collect::<BTreeSet<String>>();
+		let mut events = Vec::new();
+		if let Some(previous) = &self.loaded {
+			for (action, modules) in [
+				(ModuleAction::Load, loaded.difference(previous)),
+				(ModuleAction::Unload, previous.difference(&loaded)),
+			] {
+				events.extend(modules.map(|module| ModuleEvent {
+					action,
+					module: module.to_string(),
+				}));
+			}
+		}
+		self.loaded = Some(loaded);
+		events
+	}
+}
+
+/// Pass the module events to the given function until it returns false.
+///
+/// Loaded modules are polled at the given interval if the uevents are
+/// not available.
+pub fn watch(interval: Duration, mut handler: impl FnMut(ModuleEvent) -> bool) {
+	if let Ok(socket) = UeventSocket::open() {
+		loop {
+			let event = match socket.receive() {
+				Ok(event) => event,
+				// Events are dropped if the receive buffer is full.
+				Err(e) if e.raw_os_error() == Some(libc::ENOBUFS) => None,
+				Err(_) => break,
+			};
+			if event.is_some_and(|v| !handler(v)) {
+				return;
+			}
+		}
+	}
+	let mut watcher = ModuleWatcher::default();
+	loop {
+		if let Ok(graph) = ModuleGraph::loaded() {
+			for event in watcher.update(&graph) {
+				if !handler(event) {
+					return;
+				}
+			}
+		}
+		thread::sleep(interval);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	#[test]
+	fn test_module_event() {
+		assert_eq!(
+			Some(ModuleEvent {
+				action: ModuleAction::Unload,
+				module: String::from("snd"),
+			}),
+			ModuleEvent::parse(
+				b"remove@/module/snd\0ACTION=remove\0DEVPATH=/module/snd\0\
+				SUBSYSTEM=module\0SEQNUM=4242\0"
+			)
+		);
+		assert_eq!(
+			None,
+			ModuleEvent::parse(
+				b"add@/devices/virtual/net/tun0\0ACTION=add\0\
+				DEVPATH=/devices/virtual/net/tun0\0SUBSYSTEM=net\0"
+			)
+		);
+		assert_eq!("load", ModuleAction::Load.to_string());
+		let mut watcher = ModuleWatcher::default();
+		assert!(watcher
+			.update(&ModuleGraph::parse("a 1 0 - Live 0x0\nb 1 0 - Live 0x0\n"))
+			.is_empty());
+		assert_eq!(
+			vec![
+				ModuleEvent {
+					action: ModuleAction::Load,
+					module: String::from("c"),
+				},
+				ModuleEvent {
+					action: ModuleAction::Unload,
+					module: String::from("a"),
+				},
+			],
+			watcher
+				.update(&ModuleGraph::parse("b 1 0 - Live 0x0\nc 1 0 - Live 0x0\n"))
+		);
+	}
+}
@@ -1,6 +1,12 @@
-#![allow(clippy::tabs_in_doc_comments)]
+#![allow(
+	clippy::tabs_in_doc_comments,
+	clippy::collapsible_match,
+	clippy::manual_saturating_arithmetic
+)]
+#![cfg_attr(test, allow(deprecated, unused_must_use))]
 
 pub mod app;
+pub mod control;
 pub mod event;
 pub mod kernel;
 pub mod widgets;
@@ -10,7 +16,9 @@ pub mod args;
 pub mod style;
 
 use crate::app::{App, Block, InputMode, ScrollDirection};
+use crate::control::ControlState;
 use crate::kernel::cmd::ModuleCommand;
+use crate::kernel::lkm::ModuleView;
 use crate::kernel::Kernel;
 use enum_iterator::Sequence;
 use event::{Event, Events};
@@ -31,9 +39,29 @@ where
 	B: Backend,
 {
 	// Configure the application.
-	let mut app = App::new(Block::ModuleTable, kernel.modules.style.clone());
+	let mut app = App::new(
+		Block::ModuleTable,
+		kernel.modules.style.clone(),
+		kernel.modules.read_only,
+	);
+	// Clients and the prepared command of the control socket.
+	let mut control_state = ControlState::default();
 	// Draw terminal and render the widgets.
 	loop {
+		// Show the module information fetched in the background.
+		kernel.modules.receive_info();
+		// Redraw the terminal and discard the keys that are pressed while a
+		// command was running outside of the terminal UI.
+		if kernel.modules.suspended {
+			kernel.modules.suspended = false;
+			terminal.clear()?;
+			let pending = events.rx.try_iter().collect::<Vec<Event<Key>>>();
+			for event in pending {
+				if !matches!(event, Event::Input(_)) {
+					let _ = events.tx.send(event);
+				}
+			}
+		}
 		terminal.draw(|frame| {
 			let chunks = Layout::default()
 				.direction(Direction::Vertical)
@@ -77,11 +105,17 @@ where
 								.as_ref(),
 							)
 							.split(chunks[0]);
-						app.draw_user_input(frame, chunks[0], &events.tx);
+						app.draw_user_input(
+							frame,
+							chunks[0],
+							kernel.modules.args.search_mode(),
+							&events.tx,
+						);
 						app.draw_kernel_info(
 							frame,
 							chunks[1],
 							&kernel.info.current_info,
+							kernel.modules.mode(),
 						);
 					}
 					if app.block_size.info != 100 {
@@ -105,6 +139,110 @@ where
 				if app.input_mode.is_none() {
 					// Default input mode.
 					match input {
+						// Type the name of the protected module to confirm.
+						_ if kernel.modules.confirmation.is_some() => match input {
+							Key::Char('\n') => {
+								if !kernel.modules.is_confirmed() {
+									kernel.modules.cancel_execution();
+									app.selected_block = Block::ModuleTable;
+								} else if kernel.modules.execute_command() {
+									events
+										.tx
+										.send(Event::Input(Key::Char('r')))
+										.unwrap();
+								}
+							}
+							Key::Char(c) => kernel.modules.push_confirmation(c),
+							Key::Backspace | Key::Ctrl('h') => {
+								kernel.modules.pop_confirmation()
+							}
+							Key::Esc | Key::Ctrl('c') => {
+								kernel.modules.cancel_execution();
+								app.selected_block = Block::ModuleTable;
+							}
+							_ => {}
+						},
+						// Edit the options of the module.
+						_ if kernel.modules.options_editor.is_some()
+							&& kernel.modules.command.is_none() =>
+						{
+							let is_editing = kernel
+								.modules
+								.options_editor
+								.as_ref()
+								.is_some_and(|editor| editor.input.is_some());
+							match input {
+								Key::Char('\n') => kernel
+									.modules
+									.edit_options(|editor| editor.edit()),
+								Key::Char(c) if is_editing => {
+									kernel.modules.edit_options(|editor| {
+										editor.input.get_or_insert_default().push(c)
+									})
+								}
+								Key::Backspace | Key::Ctrl('h') if is_editing => {
+									kernel.modules.edit_options(|editor| {
+										editor.input.get_or_insert_default().pop();
+									})
+								}
+								Key::Esc | Key::Ctrl('c') if is_editing => kernel
+									.modules
+									.edit_options(|editor| editor.input = None),
+								Key::Up | Key::Char('k') | Key::Char('K') => kernel
+									.modules
+									.edit_options(|editor| editor.previous()),
+								Key::Down | Key::Char('j') | Key::Char('J') => {
+									kernel
+										.modules
+										.edit_options(|editor| editor.next())
+								}
+								Key::Char('-')
+								| Key::Char('u')
+								| Key::Backspace
+								| Key::Delete => kernel
+									.modules
+									.edit_options(|editor| editor.clear()),
+								Key::Char('s') | Key::Char('S') | Key::Ctrl('s') => {
+									kernel.modules.save_options()
+								}
+								Key::Char('e')
+								| Key::Char('q')
+								| Key::Esc
+								| Key::Ctrl('c') => kernel.modules.show_options_editor(),
+								_ => {}
+							}
+						}
+						// Ignore the keys of the commands in read-only mode.
+						Key::Ctrl('l')
+						| Key::Ctrl('u')
+						| Key::Alt('c')
+						| Key::Alt('C')
+						| Key::Char('e')
+						| Key::Char('E')
+						| Key::Char('u')
+						| Key::Char('U')
+						| Key::Char('-')
+						| Key::Backspace
+						| Key::Ctrl('h')
+						| Key::Char('x')
+						| Key::Char('X')
+						| Key::Char('b')
+						| Key::Char('B')
+						| Key::Delete
+						| Key::Ctrl('r')
+						| Key::Ctrl('R')
+						| Key::Alt('r')
+						| Key::Alt('R')
+						| Key::Char('y')
+						| Key::Char('Y')
+						| Key::Ctrl('y')
+						| Key::Char('+')
+						| Key::Char('i')
+						| Key::Char('I')
+						| Key::Insert
+						| Key::Char('z')
+						| Key::Char('Z')
+							if kernel.modules.read_only => {}
 						// Quit.
 						Key::Char('q')
 						| Key::Char('Q')
@@ -146,6 +284,13 @@ where
 								Block::ModuleTable => {
 									kernel.modules.scroll_list(ScrollDirection::Up)
 								}
+								Block::ModuleInfo
+									if kernel.modules.dependency_tree.is_some() =>
+								{
+									kernel
+										.modules
+										.scroll_dependency_tree(ScrollDirection::Up)
+								}
 								Block::ModuleInfo => kernel.modules.scroll_mod_info(
 									ScrollDirection::Up,
 									input == Key::Alt('k') || input == Key::Alt('K'),
@@ -176,6 +321,13 @@ where
 								Block::ModuleTable => {
 									kernel.modules.scroll_list(ScrollDirection::Down)
 								}
+								Block::ModuleInfo
+									if kernel.modules.dependency_tree.is_some() =>
+								{
+									kernel.modules.scroll_dependency_tree(
+										ScrollDirection::Down,
+									)
+								}
 								Block::ModuleInfo => kernel.modules.scroll_mod_info(
 									ScrollDirection::Down,
 									input == Key::Alt('j') || input == Key::Alt('J'),
@@ -267,6 +419,28 @@ where
 								.modules
 								.scroll_mod_info(ScrollDirection::Up, false)
 						}
+						// Expand or collapse the selected node of the dependency tree.
+						Key::Char(' ')
+							if kernel.modules.dependency_tree.is_some() =>
+						{
+							app.selected_block = Block::ModuleInfo;
+							kernel.modules.toggle_dependency_node();
+						}
+						// Mark or unmark the selected module.
+						Key::Char(' ')
+							if app.selected_block == Block::ModuleTable =>
+						{
+							kernel.modules.toggle_mark();
+						}
+						// Mark the modules up to the selected module.
+						Key::Char('V') => {
+							app.selected_block = Block::ModuleTable;
+							kernel.modules.mark_range();
+						}
+						// Clear the marked modules.
+						Key::Alt('v') | Key::Alt('V') => {
+							kernel.modules.marked.clear()
+						}
 						// Scroll module information down.
 						Key::Char('>') | Key::Char(' ') => {
 							app.selected_block = Block::ModuleInfo;
@@ -274,14 +448,27 @@ where
 								.modules
 								.scroll_mod_info(ScrollDirection::Down, false)
 						}
+						// Switch between the loaded and available modules.
+						Key::Char('a') | Key::Char('A') => {
+							app.selected_block = Block::ModuleTable;
+							let _ = kernel.modules.switch_view();
+						}
 						// Show the next kernel information.
 						Key::Char('\\') | Key::Char('\t') | Key::BackTab => {
 							kernel.info.next();
 						}
-						// Display the dependent modules.
+						// Display the dependency tree.
 						Key::Char('d') | Key::Alt('d') => {
-							app.show_dependent_modules(&mut kernel.modules);
+							app.selected_block = Block::ModuleInfo;
+							kernel.modules.show_dependency_tree();
+						}
+						// Display the options editor.
+						Key::Char('e') | Key::Char('E') => {
+							app.selected_block = Block::ModuleInfo;
+							kernel.modules.show_options_editor();
 						}
+						// Enable or disable the dry-run mode.
+						Key::Ctrl('y') => kernel.modules.toggle_dry_run(),
 						// Clear the kernel ring buffer.
 						Key::Ctrl('l')
 						| Key::Ctrl('u')
@@ -299,7 +486,11 @@ where
 						| Key::Backspace
 						| Key::Ctrl('h') => {
 							kernel.modules.set_current_command(
-								ModuleCommand::Unload,
+								if kernel.modules.view == ModuleView::Config {
+									ModuleCommand::Unblacklist
+								} else {
+									ModuleCommand::Unload
+								},
 								String::new(),
 							);
 						}
@@ -309,10 +500,22 @@ where
 						| Key::Char('b')
 						| Key::Char('B')
 						| Key::Delete => {
-							kernel.modules.set_current_command(
-								ModuleCommand::Blacklist,
-								String::new(),
-							);
+							if kernel.modules.view == ModuleView::Config {
+								// Toggle the configuration entry at its source.
+								if let Some(module) =
+									kernel.modules.list.get(kernel.modules.index)
+								{
+									kernel.modules.set_current_command(
+										ModuleCommand::Toggle,
+										module[1].to_string(),
+									);
+								}
+							} else {
+								kernel.modules.set_current_command(
+									ModuleCommand::Blacklist,
+									String::new(),
+								);
+							}
 						}
 						// Reload kernel module.
 						Key::Ctrl('r')
@@ -324,6 +527,16 @@ where
 								String::new(),
 							);
 						}
+						// Undo the last module operation.
+						Key::Char('z') | Key::Char('Z') => {
+							app.selected_block = Block::ModuleInfo;
+							kernel.modules.undo();
+						}
+						// Show the history of the module operations.
+						Key::Alt('z') | Key::Alt('Z') => {
+							app.selected_block = Block::ModuleInfo;
+							kernel.modules.show_history();
+						}
 						// Execute the current command.
 						Key::Char('y') | Key::Char('Y') => {
 							if kernel.modules.execute_command() {
@@ -341,8 +554,9 @@ where
 						}
 						// Copy the data in selected block to clipboard.
 						Key::Char('c') | Key::Char('C') => {
+							let selected_names = kernel.modules.selected_names();
 							app.set_clipboard_contents(match app.selected_block {
-								Block::ModuleTable => &kernel.modules.current_name,
+								Block::ModuleTable => &selected_names,
 								Block::ModuleInfo => {
 									&kernel.modules.current_info.raw_text
 								}
@@ -393,16 +607,58 @@ where
 										.map(|(v, _)| v.as_ref())
 									{
 										Some("dependent") => {
-											app.show_dependent_modules(
-												&mut kernel.modules,
-											);
+											app.selected_block = Block::ModuleInfo;
+											kernel.modules.show_dependency_tree();
+										}
+										Some("edit") => {
+											app.selected_block = Block::ModuleInfo;
+											kernel.modules.show_options_editor();
+										}
+										Some("dryrun") => {
+											kernel.modules.toggle_dry_run()
+										}
+										Some("log") => {
+											app.selected_block = Block::ModuleInfo;
+											kernel.modules.show_dry_run_log();
+										}
+										Some("undo") => {
+											app.selected_block = Block::ModuleInfo;
+											kernel.modules.undo();
+										}
+										Some("history") => {
+											app.selected_block = Block::ModuleInfo;
+											kernel.modules.show_history();
+										}
+										Some("graph") => {
+											app.selected_block = Block::ModuleInfo;
+											kernel.modules.show_dependency_graph();
 										}
 										Some("copy") => app.set_clipboard_contents(
-											&kernel.modules.current_name,
+											&kernel.modules.selected_names(),
 										),
 										_ => {}
 									}
 								}
+							} else if input == Key::Char('\n')
+								&& app.selected_block == Block::ModuleInfo
+								&& kernel.modules.dependency_tree.is_some()
+							{
+								// Select the module of the dependency tree node.
+								if kernel.modules.select_dependency_node() {
+									app.selected_block = Block::ModuleTable;
+								}
+							} else if kernel.modules.view == ModuleView::Available
+								&& matches!(
+									input,
+									Key::Char('+')
+										| Key::Char('i') | Key::Char('I')
+										| Key::Insert
+								) {
+								// Load the selected module in the available modules.
+								kernel.modules.set_current_command(
+									ModuleCommand::Load,
+									String::new(),
+								);
 							} else {
 								app.selected_block = Block::UserInput;
 								app.input_mode = match input {
@@ -445,6 +701,10 @@ where
 							if app.input_mode.is_none() {
 								app.input_mode = InputMode::last().unwrap();
 							}
+							// Skip the load mode in read-only mode.
+							if kernel.modules.read_only {
+								app.input_mode = InputMode::Search;
+							}
 							app.input_query = String::new();
 						}
 						// Switch to the next input mode.
@@ -455,8 +715,16 @@ where
 									.and_then(|v| v.next())
 									.unwrap(),
 							};
+							if kernel.modules.read_only {
+								app.input_mode = InputMode::Search;
+							}
 							app.input_query = String::new();
 						}
+						// Switch to the next search mode.
+						Key::Ctrl('f') => {
+							kernel.modules.args.next_search_mode();
+							kernel.modules.index = 0;
+						}
 						// Copy input query to the clipboard.
 						Key::Ctrl('c') => {
 							let query = app.input_query.clone();
@@ -467,6 +735,14 @@ where
 							let clipboard_contents = app.get_clipboard_contents();
 							app.input_query += &clipboard_contents;
 						}
+						// Complete the module name to load.
+						Key::Char('\t') if app.input_mode == InputMode::Load => {
+							if let Some(module_name) =
+								kernel.modules.complete_module_name(&app.input_query)
+							{
+								app.input_query = module_name;
+							}
+						}
 						// Exit user input mode.
 						Key::Char('\n')
 						| Key::Char('\t')
@@ -536,8 +812,27 @@ where
 			}
 			// Kernel events.
 			Event::Kernel(logs) => {
+				control_state.notify_logs(&logs);
 				kernel.logs.output = logs;
 			}
+			// Requests of the control socket.
+			Event::Control(request)
+				if control::handle_request(
+					&request,
+					&mut app,
+					&mut kernel,
+					&mut control_state,
+				) =>
+			{
+				app.refresh();
+				kernel.refresh();
+			}
+			// Module load and unload events.
+			Event::Module(event) => {
+				control_state.notify_module(&event);
+				kernel.modules.add_module_event(event);
+			}
+			Event::Tick => kernel.modules.expire_module_events(),
 			_ => {}
 		}
 	}
@@ -571,11 +866,42 @@ mod tests {
 				Key::Ctrl('x'),
 				Key::Char('x'),
 				Key::Char('n'),
+				Key::Char(' '),
+				Key::Down,
+				Key::Char('V'),
+				Key::Char('u'),
+				Key::Char('n'),
+				Key::Alt('v'),
+				Key::Char('d'),
+				Key::Down,
+				Key::Down,
+				Key::Char(' '),
+				Key::Char(' '),
+				Key::Up,
+				Key::Char('\n'),
+				Key::Char('d'),
 				Key::Char('d'),
+				Key::Char('e'),
+				Key::Down,
+				Key::Char('\n'),
+				Key::Char('1'),
+				Key::Backspace,
+				Key::Char('\n'),
+				Key::Delete,
+				Key::Char('s'),
+				Key::Char('n'),
+				Key::Char('e'),
+				Key::Ctrl('y'),
+				Key::Char('x'),
+				Key::Char('y'),
+				Key::Ctrl('y'),
 				Key::Ctrl('l'),
 				Key::Char('u'),
 				Key::Ctrl('r'),
 				Key::Char('y'),
+				Key::Alt('z'),
+				Key::Char('z'),
+				Key::Char('n'),
 				Key::PageUp,
 				Key::PageDown,
 				Key::Alt('l'),
@@ -586,6 +912,15 @@ mod tests {
 				Key::Char('m'),
 				Key::Down,
 				Key::Char('\n'),
+				Key::Char('a'),
+				Key::Char('+'),
+				Key::Char('n'),
+				Key::Char('a'),
+				Key::Char('x'),
+				Key::Char('n'),
+				Key::Char('u'),
+				Key::Char('n'),
+				Key::Char('a'),
 			] {
 				send_key(&tx, key);
 			}
@@ -615,6 +950,7 @@ mod tests {
 				Key::Backspace,
 				Key::Ctrl('c'),
 				Key::Ctrl('v'),
+				Key::Ctrl('f'),
 				Key::Char('a'),
 				Key::Char('\n'),
 				Key::Char('\n'),
@@ -623,6 +959,7 @@ mod tests {
 				Key::Esc,
 				Key::Char('i'),
 				Key::Char('x'),
+				Key::Char('\t'),
 				Key::Char('\n'),
 			] {
 				send_key(&tx, key);
@@ -1,11 +1,21 @@
 use kmon::args;
+use kmon::control;
 use kmon::event::Events;
+use kmon::kernel::details;
+use kmon::kernel::exporter;
+use kmon::kernel::graph;
+use kmon::kernel::list;
+use kmon::kernel::log;
+use kmon::kernel::profile;
+use kmon::kernel::snapshot;
 use kmon::kernel::Kernel;
 use kmon::util;
 use ratatui::backend::TermionBackend;
 use ratatui::Terminal;
 use std::error::Error;
+use std::fs;
 use std::io::stdout;
+use std::path::Path;
 use termion::input::MouseTerminal;
 use termion::raw::IntoRawMode;
 use termion::screen::IntoAlternateScreen;
@@ -13,6 +23,16 @@ use termion::screen::IntoAlternateScreen;
 /// Entry point.
 fn main() -> Result<(), Box<dyn Error>> {
 	let args = args::get_args().get_matches();
+	match args.subcommand() {
+		Some(("graph", matches)) => return graph::run(matches),
+		Some(("list", matches)) => return list::run(&args, matches),
+		Some(("info", matches)) => return details::run(matches),
+		Some(("logs", matches)) => return log::run(matches),
+		Some(("serve", matches)) => return exporter::serve(&args, matches),
+		Some(("apply", matches)) => return profile::apply(&args, matches),
+		Some(("snapshot", matches)) => return snapshot::run(matches),
+		_ => {}
+	}
 	let kernel = Kernel::new(&args);
 	let events = Events::new(
 		args.get_one::<String>("rate")
@@ -21,12 +41,20 @@ fn main() -> Result<(), Box<dyn Error>> {
 			.unwrap_or(250),
 		&kernel.logs,
 	);
+	let control_socket = args.get_one::<String>("control-socket").map(Path::new);
+	if let Some(path) = control_socket {
+		control::listen(path, events.tx.clone())?;
+	}
 	if !cfg!(test) {
 		util::setup_panic_hook()?;
 		let stdout = stdout().into_raw_mode()?.into_alternate_screen()?;
 		let stdout = MouseTerminal::from(stdout);
 		let backend = TermionBackend::new(stdout);
-		kmon::start_tui(Terminal::new(backend)?, kernel, &events)
+		let result = kmon::start_tui(Terminal::new(backend)?, kernel, &events);
+		if let Some(path) = control_socket {
+			let _ = fs::remove_file(path);
+		}
+		result
 	} else {
 		Ok(())
 	}
@@ -132,14 +132,14 @@ impl Style {
 
 /// Styled text that has raw and style parts
 #[derive(Debug, Default)]
-pub struct StyledText<'a> {
+pub struct StyledText {
 	pub raw_text: String,
-	pub styled_text: Text<'a>,
+	pub styled_text: Text<'static>,
 }
 
-impl<'a> StyledText<'a> {
+impl StyledText {
 	/// Get a vector of Text widget from styled text.
-	pub fn get(&'a self) -> Text<'a> {
+	pub fn get(&self) -> Text<'_> {
 		if self.styled_text.lines.is_empty() {
 			Text::raw(&self.raw_text)
 		} else {
@@ -156,10 +156,10 @@ impl<'a> StyledText<'a> {
 	/// Add style to given text depending on a delimiter.
 	pub fn stylize_data(
 		&mut self,
-		text: &'a str,
+		text: &str,
 		delimiter: &str,
 		style: Style,
-	) -> Text<'a> {
+	) -> Text<'static> {
 		self.styled_text = Text::default();
 		self.raw_text = text.to_string();
 		for line in text.lines() {
@@ -172,7 +172,7 @@ impl<'a> StyledText<'a> {
 			} else {
 				self.styled_text
 					.lines
-					.push(Line::from(Span::styled(line, style.default)))
+					.push(Line::from(Span::styled(line.to_string(), style.default)))
 			}
 		}
 		self.styled_text.clone()
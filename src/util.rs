@@ -1,8 +1,21 @@
 use std::error::Error;
-use std::io::{self, Write};
+use std::io::{self, Stdout, Write};
 use std::panic;
-use std::process::Command;
-use termion::raw::IntoRawMode;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock, PoisonError};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use termion::raw::{IntoRawMode, RawTerminal};
+
+/// Terminal that restores the attributes before the raw mode when suspended
+static TERMINAL: OnceLock<RawTerminal<Stdout>> = OnceLock::new();
+
+/// Flag that pauses reading the terminal input
+static INPUT_PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// Lock that is held while the terminal input is read
+static INPUT_LOCK: Mutex<()> = Mutex::new(());
 
 /// Macro for concise initialization of hashmap
 macro_rules! map {
@@ -14,6 +27,9 @@ macro_rules! map {
 }
 
 /// Array of the key bindings
+///
+/// Actions of the same key are separated by "; " and followed by the
+/// context that they apply in, the last action applies otherwise.
 pub const KEY_BINDINGS: &[(&str, &str)] = &[
 	("'?', f1", "help"),
 	("right/left, h/l", "switch between blocks"),
@@ -22,24 +38,250 @@ pub const KEY_BINDINGS: &[(&str, &str)] = &[
 	("</>", "scroll up/down [module information]"),
 	("alt-h/l", "scroll right/left [kernel activities]"),
 	("ctrl-t/b, home/end", "scroll to top/bottom [module list]"),
+	("a", "switch between loaded/available/configured modules"),
 	("alt-e/s", "expand/shrink the selected block"),
 	("ctrl-x", "change the block position"),
 	("ctrl-l/u, alt-c", "clear the kernel ring buffer"),
-	("d, alt-d", "show the dependent modules"),
+	("d, alt-d", "show the dependency tree"),
+	(
+		"space",
+		"expand/collapse the node [dependency tree]; \
+		mark/unmark the module [module list]",
+	),
+	("e", "edit the options at boot"),
+	(
+		"enter",
+		"edit/set the value [options editor]; \
+		select the module of the node [dependency tree]; \
+		search a kernel module",
+	),
+	(
+		"s",
+		"save the options [options editor]; search a kernel module",
+	),
+	("/", "search a kernel module"),
 	("1..9", "jump to the dependent module"),
-	("\\, tab, backtab", "show the next kernel information"),
-	("/, s, enter", "search a kernel module"),
+	(
+		"tab",
+		"complete the module name [load]; \
+		show the next kernel information",
+	),
+	("\\, backtab", "show the next kernel information"),
+	("ctrl-f", "switch the search mode [user input]"),
 	("+, i, insert", "load a kernel module"),
-	("-, u, backspace", "unload the kernel module"),
-	("x, b, delete", "blacklist the kernel module"),
+	(
+		"-, u, backspace",
+		"clear the value [options editor]; \
+		unblacklist the module [configured]; \
+		unload the kernel module",
+	),
+	(
+		"delete",
+		"clear the value [options editor]; \
+		disable/enable the entry [configured]; \
+		blacklist the kernel module",
+	),
+	(
+		"x, b",
+		"disable/enable the entry [configured]; \
+		blacklist the kernel module",
+	),
 	("ctrl-r, alt-r", "reload the kernel module"),
+	("V", "mark the modules up to the selected module"),
+	("alt-v", "clear the marked modules"),
 	("m, o", "show the options menu"),
 	("y/n", "execute/cancel the command"),
+	("z", "undo the last module operation"),
+	("alt-z", "show the operation history"),
+	("ctrl-y", "enable/disable the dry-run mode"),
 	("c/v", "copy/paste"),
 	("r, f5", "refresh"),
 	("q, ctrl-c/d, esc", "quit"),
 ];
 
+/// Return the line-based difference between two texts.
+///
+/// Lines are prefixed with '-' if removed, '+' if added and ' ' otherwise.
+pub fn diff_lines(old: &str, new: &str) -> Vec<String> {
+	let old: Vec<&str> = old.lines().collect();
+	let new: Vec<&str> = new.lines().collect();
+	// Length of the longest common subsequence of old[i..] and new[j..].
+	let mut lengths = vec![vec![0; new.len() + 1]; old.len() + 1];
+	for i in (0..old.len()).rev() {
+		for j in (0..new.len()).rev() {
+			lengths[i][j] = if old[i] == new[j] {
+				lengths[i + 1][j + 1] + 1
+			} else {
+				lengths[i + 1][j].max(lengths[i][j + 1])
+			};
+		}
+	}
+	let (mut i, mut j) = (0, 0);
+	let mut diff = Vec::new();
+	while i < old.len() || j < new.len() {
+		if i < old.len() && j < new.len() && old[i] == new[j] {
+			diff.push(format!(" {}", old[i]));
+			i += 1;
+			j += 1;
+		} else if i < old.len()
+			&& (j == new.len() || lengths[i + 1][j] >= lengths[i][j + 1])
+		{
+			diff.push(format!("-{}", old[i]));
+			i += 1;
+		} else {
+			diff.push(format!("+{}", new[j]));
+			j += 1;
+		}
+	}
+	diff
+}
+
+/// Actions of the key bindings that change the system
+pub const COMMAND_KEY_BINDINGS: &[&str] = &[
+	"clear the kernel ring buffer",
+	"edit the options at boot",
+	"edit/set the value [options editor]",
+	"clear the value [options editor]",
+	"save the options [options editor]",
+	"load a kernel module",
+	"complete the module name [load]",
+	"unload the kernel module",
+	"blacklist the kernel module",
+	"unblacklist the module [configured]",
+	"disable/enable the entry [configured]",
+	"reload the kernel module",
+	"execute/cancel the command",
+	"undo the last module operation",
+	"enable/disable the dry-run mode",
+];
+
+/// Score bonus for each matched character
+const FUZZY_MATCH_SCORE: i64 = 16;
+/// Score bonus for a match at the start of a word
+const FUZZY_BOUNDARY_BONUS: i64 = 8;
+/// Score bonus for a match that directly follows the previous match
+const FUZZY_CONSECUTIVE_BONUS: i64 = 12;
+/// Score penalty for each character skipped between two matches
+const FUZZY_GAP_PENALTY: i64 = 1;
+
+/// Match the pattern against the text as a case-insensitive subsequence.
+///
+/// Returns the score of the best alignment along with the character
+/// indices of the matched characters, or `None` if the text doesn't
+/// contain every pattern character in order.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+	let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+	let text: Vec<char> = text.chars().collect();
+	if pattern.is_empty() || pattern.len() > text.len() {
+		return None;
+	}
+	let bonus = |j: usize| {
+		if j == 0 || matches!(text[j - 1], '_' | '-' | '.' | ' ' | '/') {
+			FUZZY_BOUNDARY_BONUS
+		} else {
+			0
+		}
+	};
+	let (n, m) = (pattern.len(), text.len());
+	// Best score of matching pattern[..=i] with pattern[i] at text[j],
+	// and the text position of pattern[i - 1] for backtracking.
+	let mut scores = vec![vec![None; m]; n];
+	let mut parents = vec![vec![0; m]; n];
+	for (i, pattern_char) in pattern.iter().enumerate() {
+		// Best score of the previous row so far, reduced by the gap penalty.
+		let mut gap_best: Option<(i64, usize)> = None;
+		for j in i..m {
+			if i > 0 && j >= 2 {
+				if let Some(score) = scores[i - 1][j - 2] {
+					gap_best = match gap_best {
+						Some((best, k)) if best >= score => {
+							Some((best - FUZZY_GAP_PENALTY, k))
+						}
+						_ => Some((score - FUZZY_GAP_PENALTY, j - 2)),
+					};
+				} else if let Some((best, k)) = gap_best {
+					gap_best = Some((best - FUZZY_GAP_PENALTY, k));
+				}
+			}
+			if !text[j].to_lowercase().eq(pattern_char.to_lowercase()) {
+				continue;
+			}
+			let score = FUZZY_MATCH_SCORE + bonus(j);
+			if i == 0 {
+				scores[i][j] = Some(score - j as i64 * FUZZY_GAP_PENALTY);
+				continue;
+			}
+			let consecutive = if j >= 1 {
+				scores[i - 1][j - 1].map(|v| (v + FUZZY_CONSECUTIVE_BONUS, j - 1))
+			} else {
+				None
+			};
+			if let Some((best, k)) = match (consecutive, gap_best) {
+				(Some(c), Some(g)) => Some(if c.0 >= g.0 { c } else { g }),
+				(c, g) => c.or(g),
+			} {
+				scores[i][j] = Some(best + score);
+				parents[i][j] = k;
+			}
+		}
+	}
+	let (mut j, score) = scores[n - 1]
+		.iter()
+		.enumerate()
+		.filter_map(|(j, score)| score.map(|v| (j, v)))
+		.max_by_key(|(j, score)| (*score, std::cmp::Reverse(*j)))?;
+	let mut positions = vec![j; n];
+	for i in (1..n).rev() {
+		j = parents[i][j];
+		positions[i - 1] = j;
+	}
+	Some((score, positions))
+}
+
+/// Return the current time as seconds since the Unix epoch.
+pub fn timestamp() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|v| v.as_secs())
+		.unwrap_or_default()
+}
+
+/// Format the seconds since the Unix epoch in the RFC 3339 format.
+pub fn format_timestamp(timestamp: u64) -> String {
+	let days = (timestamp / 86400) as i64;
+	let seconds = timestamp % 86400;
+	// Convert the days since epoch to a civil date.
+	let z = days + 719468;
+	let era = z.div_euclid(146097);
+	let day_of_era = z.rem_euclid(146097);
+	let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524
+		- day_of_era / 146096)
+		/ 365;
+	let day_of_year =
+		day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let mp = (5 * day_of_year + 2) / 153;
+	let day = day_of_year - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = year_of_era + era * 400 + i64::from(month <= 2);
+	format!(
+		"{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+		seconds / 3600,
+		seconds / 60 % 60,
+		seconds % 60
+	)
+}
+
+/// Quote a string for using it as a single word in a shell command.
+pub fn shell_quote(s: &str) -> String {
+	format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Return a path in the temporary directory that is unique to the test run.
+#[cfg(test)]
+pub fn temp_path(name: &str) -> std::path::PathBuf {
+	std::env::temp_dir().join(format!("kmon-test-{}-{name}", std::process::id()))
+}
+
 /// Execute a operating system command and return its output.
 pub fn exec_cmd(cmd: &str, cmd_args: &[&str]) -> Result<String, String> {
 	match Command::new(cmd).args(cmd_args).output() {
@@ -60,12 +302,77 @@ pub fn exec_cmd(cmd: &str, cmd_args: &[&str]) -> Result<String, String> {
 	}
 }
 
+/// Wait until the terminal input can be read.
+///
+/// Returns the guard that has to be held while reading the input, or None
+/// if there is no input within the timeout or reading is paused.
+pub fn wait_input(timeout: Duration) -> Option<MutexGuard<'static, ()>> {
+	if INPUT_PAUSED.load(Ordering::SeqCst) {
+		thread::sleep(timeout);
+		return None;
+	}
+	let guard = INPUT_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+	let mut fd = libc::pollfd {
+		fd: libc::STDIN_FILENO,
+		events: libc::POLLIN,
+		revents: 0,
+	};
+	(unsafe { libc::poll(&mut fd, 1, timeout.as_millis() as libc::c_int) } > 0)
+		.then_some(guard)
+}
+
+/// Execute a operating system command outside of the terminal UI and
+/// return its exit status.
+///
+/// Raw mode and the alternate screen are left while the command is running
+/// so that it can prompt for a password on the terminal. Terminal input is
+/// not read by the UI until the command exits.
+pub fn exec_cmd_interactive(
+	message: &str,
+	cmd: &str,
+	cmd_args: &[&str],
+) -> io::Result<ExitStatus> {
+	INPUT_PAUSED.store(true, Ordering::SeqCst);
+	let _input = INPUT_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+	let mut output = io::stdout();
+	if let Some(terminal) = TERMINAL.get() {
+		let _ = write!(
+			output,
+			"{}{}",
+			termion::screen::ToMainScreen,
+			termion::cursor::Show
+		);
+		let _ = terminal.suspend_raw_mode();
+	}
+	let _ = writeln!(output, "{message}");
+	let _ = output.flush();
+	let result = Command::new(cmd)
+		.args(cmd_args)
+		.stdin(Stdio::inherit())
+		.stdout(Stdio::inherit())
+		.stderr(Stdio::inherit())
+		.status();
+	if let Some(terminal) = TERMINAL.get() {
+		let _ = terminal.activate_raw_mode();
+		let _ = write!(
+			output,
+			"{}{}",
+			termion::screen::ToAlternateScreen,
+			termion::cursor::Hide
+		);
+		let _ = output.flush();
+	}
+	INPUT_PAUSED.store(false, Ordering::SeqCst);
+	result
+}
+
 /// Sets up the panic hook for the terminal.
 ///
 /// See <https://ratatui.rs/how-to/develop-apps/panic-hooks/#termion>
 pub fn setup_panic_hook() -> Result<(), Box<dyn Error>> {
 	let raw_output = io::stdout().into_raw_mode()?;
 	raw_output.suspend_raw_mode()?;
+	let raw_output = TERMINAL.get_or_init(|| raw_output);
 
 	let panic_hook = panic::take_hook();
 	panic::set_hook(Box::new(move |panic| {
@@ -93,6 +400,29 @@ pub fn setup_panic_hook() -> Result<(), Box<dyn Error>> {
 mod tests {
 	use super::*;
 	#[test]
+	fn test_diff_lines() {
+		assert_eq!(
+			vec![" a", "-b", "+c", " d", "+e"],
+			diff_lines("a\nb\nd\n", "a\nc\nd\ne\n")
+		);
+		assert!(diff_lines("", "").is_empty());
+	}
+	#[test]
+	fn test_key_bindings() {
+		let keys = KEY_BINDINGS
+			.iter()
+			.flat_map(|(key, _)| key.split(", "))
+			.collect::<Vec<&str>>();
+		for key in &keys {
+			assert_eq!(1, keys.iter().filter(|k| k == &key).count(), "{key}");
+		}
+		for action in COMMAND_KEY_BINDINGS {
+			assert!(KEY_BINDINGS
+				.iter()
+				.any(|(_, desc)| desc.split("; ").any(|a| a == *action)));
+		}
+	}
+	#[test]
 	fn test_exec_cmd() {
 		assert_eq!("test", exec_cmd("printf", &["test"]).unwrap());
 		assert_eq!(
@@ -103,5 +433,28 @@ mod tests {
 			"err",
 			exec_cmd("cat", &["-x"]).unwrap_or(String::from("err"))
 		);
+		assert_eq!(
+			"it's",
+			exec_cmd("sh", &["-c", &format!("printf {}", shell_quote("it's"))])
+				.unwrap()
+		);
+		assert!(exec_cmd_interactive("", "true", &[]).unwrap().success());
+		assert!(!exec_cmd_interactive("", "false", &[]).unwrap().success());
+	}
+	#[test]
+	fn test_fuzzy_match() {
+		assert_eq!(None, fuzzy_match("drmnv", "nvidia_drm"));
+		assert_eq!(None, fuzzy_match("", "nvidia_drm"));
+		assert_eq!(
+			Some(vec![0, 1, 7, 8, 9]),
+			fuzzy_match("nvdrm", "nvidia_drm").map(|(_, v)| v)
+		);
+		assert_eq!(
+			Some(vec![4, 5, 6]),
+			fuzzy_match("HDA", "snd_hda_intel").map(|(_, v)| v)
+		);
+		let score = |pattern, text| fuzzy_match(pattern, text).unwrap().0;
+		assert!(score("nvdrm", "nvidia_drm") > score("nvdrm", "nvidia_modeset_drm"));
+		assert!(score("ext4", "ext4") > score("ext4", "xt_ext4_helper"));
 	}
 }